@@ -237,16 +237,25 @@ void handleCommand(String cmd) {
     // Print to serial for legacy behavior
     Serial.println("SENSORS:HC-SR04:7-6,LED:5,LCD:0x27");
     Serial.println("BOARD:Arduino UNO R4 WiFi");
+    Serial.print("ID:");
+    Serial.println(BOARD_ID);
     Serial.println("FIRMWARE:1.0.1");
     // Also publish a compact info over MQTT
     String topic = String("miniverse/") + BOARD_ID + "/info/state";
-    client.publish(topic.c_str(), "SENSORS:HC-SR04:7-6,LED:5,LCD:0x27;BOARD:Arduino UNO R4 WiFi;FIRMWARE:1.0.1");
+    String info = String("SENSORS:HC-SR04:7-6,LED:5,LCD:0x27;BOARD:Arduino UNO R4 WiFi;ID:") + BOARD_ID + ";FIRMWARE:1.0.1";
+    client.publish(topic.c_str(), info.c_str());
   }
   else if (up == "/HELP" || up == "HELP") {
-    Serial.println("CMDS: TEMP, DISTANCE, LIGHT ON|OFF|TOGGLE, SET LIGHT <0-255>, LCD CLEAR, LCD SHOW \"a\" [\"b\"], INFO, VERSION, ABOUT");
+    const char* cmds = "CMDS: TEMP, DISTANCE, LIGHT ON|OFF|TOGGLE, SET LIGHT <0-255>, LCD CLEAR, LCD SHOW \"a\" [\"b\"], INFO, VERSION, ABOUT";
+    Serial.println(cmds);
+    // Capability list for the backend handshake
+    String topic = String("miniverse/") + BOARD_ID + "/info/state";
+    client.publish(topic.c_str(), cmds);
   }
   else if (up == "/VERSION" || up == "VERSION") {
     Serial.println("VERSION:1.0.1");
+    String topic = String("miniverse/") + BOARD_ID + "/info/state";
+    client.publish(topic.c_str(), "VERSION:1.0.1");
   }
   else if (up == "/ABOUT" || up == "ABOUT") {
     Serial.println("ABOUT:Miniverse Arduino Firmware");
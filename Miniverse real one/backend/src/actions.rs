@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Identity;
+use crate::events::SystemEvent;
+use crate::serial::handle_serial_command_with_transport;
+use crate::shell::{Invocation, Spec};
+use crate::state::AppState;
+
+/// Something the backend does on its own: used by scheduled jobs and rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Action {
+    // Any terminal command, run with the global transport
+    Command { command: String },
+    Publish { topic: String, payload: String },
+}
+
+impl Action {
+    /// `publish <topic> <payload>` becomes a direct MQTT publish, anything else a terminal command
+    pub fn parse(text: &str) -> Result<Self, String> {
+        const PUBLISH: Spec = Spec::new("publish <topic> <payload>", 1, None).options_first();
+        let text = text.trim();
+        let inv = Invocation::parse(text)?;
+        if inv.name() != "publish" {
+            return Ok(Action::Command { command: text.to_string() });
+        }
+        let args = inv.args(&PUBLISH)?;
+        Ok(Action::Publish { topic: args.get(0).unwrap_or("").to_string(), payload: args.rest(1) })
+    }
+
+    /// The MQTT topic this action publishes to, directly or through `mqtt pub`
+    pub fn publish_topic(&self) -> Option<String> {
+        match self {
+            Action::Publish { topic, .. } => Some(topic.clone()),
+            Action::Command { command } => {
+                let inv = Invocation::parse(command).ok()?;
+                let sub = inv.subcommand().filter(|s| inv.name() == "mqtt" && matches!(s.name(), "pub" | "publish"))?;
+                sub.words().first().map(|t| t.to_string())
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Action::Command { command } => command.clone(),
+            Action::Publish { topic, payload } => format!("publish {} {}", topic, payload),
+        }
+    }
+
+    /// Run the action and report (ok, output)
+    pub async fn run(&self, state: &AppState) -> (bool, String) {
+        match self {
+            Action::Command { command } => match handle_serial_command_with_transport(command, state, None, &Identity::system()).await {
+                SystemEvent::Output { content } => (true, content),
+                SystemEvent::Error { message, .. } => (false, message),
+                other => (true, serde_json::to_string(&other).unwrap_or_default()),
+            },
+            Action::Publish { topic, payload } => {
+                let mqtt = state.mqtt.read().await;
+                match mqtt.publish(topic, payload.as_bytes()).await {
+                    Ok(_) => (true, format!("published to {}", topic)),
+                    Err(e) => (false, e),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    // No traffic from the board for `timeout_secs`; board "*" watches every board seen so far
+    Silent { board: String, timeout_secs: u64 },
+    // A reading outside [min, max]; either bound may be omitted
+    Range {
+        sensor: String,
+        #[serde(default)]
+        board: Option<String>,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+}
+
+impl AlertCondition {
+    pub fn describe(&self) -> String {
+        match self {
+            AlertCondition::Silent { board, timeout_secs } => format!("{} silent for {}s", board, timeout_secs),
+            AlertCondition::Range { sensor, board, min, max } => {
+                format!("{} on {} outside {}", sensor, board.as_deref().unwrap_or("*"), bounds(*min, *max))
+            }
+        }
+    }
+}
+
+/// `[min, max]` with open ends shown as infinities
+pub fn bounds(min: Option<f64>, max: Option<f64>) -> String {
+    format!(
+        "[{}, {}]",
+        min.map(|v| v.to_string()).unwrap_or_else(|| "-inf".into()),
+        max.map(|v| v.to_string()).unwrap_or_else(|| "inf".into())
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertDef {
+    pub name: String,
+    pub condition: AlertCondition,
+    // Sink names to notify; empty means every configured sink
+    #[serde(default)]
+    pub sinks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Firing,
+    Acked,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRecord {
+    pub id: u32,
+    pub name: String,
+    pub board: String,
+    pub message: String,
+    pub status: AlertStatus,
+    pub raised_at: DateTime<Local>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acked_at: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Local>>,
+}
+
+impl AlertRecord {
+    pub fn summary(&self) -> String {
+        let status = match self.status {
+            AlertStatus::Firing => "FIRING",
+            AlertStatus::Acked => "ACKED",
+            AlertStatus::Resolved => "RESOLVED",
+        };
+        format!(
+            "#{} [{}] {} ({}) {} since {}",
+            self.id,
+            status,
+            self.name,
+            self.board,
+            self.message,
+            self.raised_at.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+/// Alert history: open alerts plus the most recent resolved ones
+#[derive(Debug, Default)]
+pub struct AlertBook {
+    records: Vec<AlertRecord>,
+    next_id: u32,
+    // (definition, board) pairs resolved by hand; not raised again until their condition clears
+    held: HashSet<(String, String)>,
+}
+
+// Resolved alerts kept for `alerts all`
+const RESOLVED_HISTORY: usize = 50;
+
+impl AlertBook {
+    pub fn new() -> Self {
+        Self { records: Vec::new(), next_id: 1, held: HashSet::new() }
+    }
+
+    pub fn records(&self) -> &[AlertRecord] {
+        &self.records
+    }
+
+    /// The open (firing or acked) alert for a definition and board, if any
+    pub fn open(&self, name: &str, board: &str) -> Option<&AlertRecord> {
+        self.records
+            .iter()
+            .find(|r| r.name == name && r.board == board && r.status != AlertStatus::Resolved)
+    }
+
+    pub fn raise(&mut self, name: &str, board: &str, message: String) -> AlertRecord {
+        let record = AlertRecord {
+            id: self.next_id,
+            name: name.to_string(),
+            board: board.to_string(),
+            message,
+            status: AlertStatus::Firing,
+            raised_at: Local::now(),
+            acked_at: None,
+            resolved_at: None,
+        };
+        self.next_id += 1;
+        self.records.push(record.clone());
+        record
+    }
+
+    pub fn ack(&mut self, id: u32) -> Result<AlertRecord, String> {
+        let r = self.records.iter_mut().find(|r| r.id == id).ok_or(format!("No alert #{}", id))?;
+        if r.status != AlertStatus::Firing {
+            return Err(format!("Alert #{} is not firing", id));
+        }
+        r.status = AlertStatus::Acked;
+        r.acked_at = Some(Local::now());
+        Ok(r.clone())
+    }
+
+    pub fn resolve(&mut self, id: u32) -> Result<AlertRecord, String> {
+        let r = self.records.iter_mut().find(|r| r.id == id).ok_or(format!("No alert #{}", id))?;
+        if r.status == AlertStatus::Resolved {
+            return Err(format!("Alert #{} is already resolved", id));
+        }
+        r.status = AlertStatus::Resolved;
+        r.resolved_at = Some(Local::now());
+        let resolved = r.clone();
+        self.prune();
+        Ok(resolved)
+    }
+
+    /// `alerts resolve`: like `resolve`, and hold the alert until its condition clears
+    pub fn resolve_by_hand(&mut self, id: u32) -> Result<AlertRecord, String> {
+        let resolved = self.resolve(id)?;
+        self.held.insert((resolved.name.clone(), resolved.board.clone()));
+        Ok(resolved)
+    }
+
+    /// Whether a hand-resolved alert is still waiting for its condition to clear
+    pub fn is_held(&self, name: &str, board: &str) -> bool {
+        self.held.contains(&(name.to_string(), board.to_string()))
+    }
+
+    /// The condition cleared: the alert may be raised again
+    pub fn release(&mut self, name: &str, board: &str) {
+        self.held.remove(&(name.to_string(), board.to_string()));
+    }
+
+    fn prune(&mut self) {
+        let resolved = self.records.iter().filter(|r| r.status == AlertStatus::Resolved).count();
+        let mut excess = resolved.saturating_sub(RESOLVED_HISTORY);
+        self.records.retain(|r| {
+            if excess > 0 && r.status == AlertStatus::Resolved {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_resolved_alert_is_held_until_released() {
+        let mut book = AlertBook::new();
+        let id = book.raise("hot", "b1", "temp 40C".to_string()).id;
+        book.resolve_by_hand(id).unwrap();
+        assert!(book.open("hot", "b1").is_none());
+        assert!(book.is_held("hot", "b1"));
+        assert!(!book.is_held("hot", "b2"));
+        book.release("hot", "b1");
+        assert!(!book.is_held("hot", "b1"));
+    }
+
+    #[test]
+    fn automatic_resolve_does_not_hold() {
+        let mut book = AlertBook::new();
+        let id = book.raise("hot", "b1", "temp 40C".to_string()).id;
+        book.resolve(id).unwrap();
+        assert!(!book.is_held("hot", "b1"));
+        assert!(book.resolve(id).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::alerts::book::AlertStatus;
+use crate::alerts::engine::announce;
+use crate::events::SystemEvent;
+use crate::shell::{Invocation, Spec};
+use crate::state::AppState;
+
+const USAGE: &str = "Usage:\n  alerts              open alerts\n  alerts all          open and recently resolved\n  alerts ack <id>\n  alerts resolve <id> (not raised again until its condition clears)\n  alerts defs         configured definitions and sinks\n";
+
+const ACK: Spec = Spec::new("alerts ack|resolve <id>", 1, Some(1));
+
+/// `alerts [all|ack|resolve|defs]`
+pub async fn handle_alerts(inv: &Invocation, state: &AppState) -> SystemEvent {
+    let sub_inv = inv.subcommand();
+    let sub = sub_inv.as_ref().map_or("", |s| s.name());
+    match sub {
+        "" | "list" | "ls" | "all" => {
+            let book = state.alerts.read().await;
+            let shown: Vec<_> = book
+                .records()
+                .iter()
+                .filter(|r| sub == "all" || r.status != AlertStatus::Resolved)
+                .collect();
+            if shown.is_empty() {
+                return SystemEvent::Output { content: "No alerts".to_string() };
+            }
+            let mut out = format!("Alerts ({}):\n", shown.len());
+            for record in shown {
+                out.push_str(&format!("{}\n", record.summary()));
+            }
+            SystemEvent::Output { content: out }
+        }
+        "ack" | "resolve" => {
+            let parsed = sub_inv.as_ref().map(|s| {
+                let args = s.args(&ACK)?;
+                args.get(0).unwrap_or("").trim_start_matches('#').parse::<u32>().map_err(|e| args.error_at(0, &format!("Invalid alert id: {}", e)))
+            });
+            let id = match parsed {
+                Some(Ok(id)) => id,
+                Some(Err(message)) => return SystemEvent::Error { source: "cli".to_string(), message },
+                None => return SystemEvent::Output { content: USAGE.to_string() },
+            };
+            let result = {
+                let mut book = state.alerts.write().await;
+                if sub == "ack" { book.ack(id) } else { book.resolve_by_hand(id) }
+            };
+            match result {
+                Ok(record) => {
+                    announce(state, &record);
+                    SystemEvent::Output { content: record.summary() }
+                }
+                Err(e) => SystemEvent::Error { source: "alerts".to_string(), message: e },
+            }
+        }
+        "defs" => {
+            let cfg = &state.config.alerts;
+            if cfg.definitions.is_empty() {
+                return SystemEvent::Output { content: "No alert definitions configured".to_string() };
+            }
+            let mut out = format!("Alert definitions ({}):\n", cfg.definitions.len());
+            for def in &cfg.definitions {
+                let cond = def.condition.describe();
+                let sinks = if def.sinks.is_empty() { "all sinks".to_string() } else { def.sinks.join(", ") };
+                out.push_str(&format!("  {:<16} {} -> {}\n", def.name, cond, sinks));
+            }
+            let names: Vec<&str> = cfg.sinks.iter().map(|s| s.name()).collect();
+            out.push_str(&format!("Sinks: {}\n", if names.is_empty() { "none".to_string() } else { names.join(", ") }));
+            SystemEvent::Output { content: out }
+        }
+        other => {
+            let message = sub_inv.as_ref().map_or_else(String::new, |s| s.error_at_name(&format!("Unknown alerts subcommand '{}'", other)));
+            SystemEvent::Error { source: "cli".to_string(), message: format!("{}\n{}", message, USAGE) }
+        }
+    }
+}
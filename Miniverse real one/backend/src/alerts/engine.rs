@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::alerts::book::{bounds, AlertCondition, AlertDef, AlertRecord, AlertStatus};
+use crate::events::SystemEvent;
+use crate::readings::normalize_sensor;
+use crate::state::AppState;
+
+/// Watch board traffic and readings, raising and auto-resolving configured alerts
+pub fn spawn_alerts(state: AppState) {
+    tokio::spawn(async move {
+        let defs = state.config.alerts.definitions.clone();
+        if defs.is_empty() {
+            log::info!("Alerts: no definitions configured");
+            return;
+        }
+        log::info!("Alerts engine started ({} definitions)", defs.len());
+
+        let mut rx = state.subscribe();
+        let mut ticker = tokio::time::interval(Duration::from_millis(state.config.alerts.check_interval_ms));
+        // Explicitly named boards count as seen at startup, so a board that never shows up still alerts
+        let start = Instant::now();
+        let mut last_seen: HashMap<String, Instant> = defs
+            .iter()
+            .filter_map(|d| match &d.condition {
+                AlertCondition::Silent { board, .. } if board != "*" => Some((board.clone(), start)),
+                _ => None,
+            })
+            .collect();
+
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(event) => on_event(&state, &defs, &event, &mut last_seen).await,
+                    Err(RecvError::Lagged(n)) => log::warn!("Alerts engine skipped {} events", n),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => check_silence(&state, &defs, &last_seen).await,
+            }
+        }
+    });
+}
+
+async fn on_event(state: &AppState, defs: &[AlertDef], event: &SystemEvent, last_seen: &mut HashMap<String, Instant>) {
+    let board = match event {
+        SystemEvent::Reading { board, .. } => board.clone(),
+        SystemEvent::MqttMessage { topic, .. } => match topic.strip_prefix("miniverse/").and_then(|t| t.split_once('/')) {
+            Some((board, _)) => board.to_string(),
+            None => return,
+        },
+        _ => return,
+    };
+    last_seen.insert(board.clone(), Instant::now());
+
+    for def in defs {
+        match &def.condition {
+            AlertCondition::Silent { board: watched, .. } if watched == "*" || *watched == board => {
+                resolve_open(state, def, &board).await;
+            }
+            AlertCondition::Range { sensor, board: only, min, max } => {
+                let SystemEvent::Reading { sensor: got, value, unit, .. } = event else { continue };
+                if normalize_sensor(got) != normalize_sensor(sensor) || only.as_ref().is_some_and(|b| *b != board) {
+                    continue;
+                }
+                let out = min.is_some_and(|m| *value < m) || max.is_some_and(|m| *value > m);
+                if out {
+                    raise(state, def, &board, format!("{} {}{} outside {}", got, value, unit, bounds(*min, *max))).await;
+                } else {
+                    resolve_open(state, def, &board).await;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn check_silence(state: &AppState, defs: &[AlertDef], last_seen: &HashMap<String, Instant>) {
+    for def in defs {
+        let AlertCondition::Silent { board: watched, timeout_secs } = &def.condition else { continue };
+        for (board, seen) in last_seen {
+            if watched != "*" && watched != board {
+                continue;
+            }
+            let silent = seen.elapsed();
+            if silent > Duration::from_secs(*timeout_secs) {
+                raise(state, def, board, format!("no traffic for {}s", silent.as_secs())).await;
+            }
+        }
+    }
+}
+
+async fn raise(state: &AppState, def: &AlertDef, board: &str, message: String) {
+    let record = {
+        let mut book = state.alerts.write().await;
+        if book.open(&def.name, board).is_some() || book.is_held(&def.name, board) {
+            return;
+        }
+        book.raise(&def.name, board, message)
+    };
+    log::warn!("Alert raised: {}", record.summary());
+    announce(state, &record);
+}
+
+/// The condition is no longer true: resolve the open alert and lift any hold from `alerts resolve`
+async fn resolve_open(state: &AppState, def: &AlertDef, board: &str) {
+    let record = {
+        let mut book = state.alerts.write().await;
+        book.release(&def.name, board);
+        let Some(id) = book.open(&def.name, board).map(|r| r.id) else { return };
+        match book.resolve(id) {
+            Ok(r) => r,
+            Err(_) => return,
+        }
+    };
+    log::info!("Alert resolved: {}", record.summary());
+    announce(state, &record);
+}
+
+/// Broadcast the alert's current lifecycle state and notify its sinks in the background
+pub(super) fn announce(state: &AppState, record: &AlertRecord) {
+    let (id, name, board) = (record.id, record.name.clone(), record.board.clone());
+    state.broadcast(match record.status {
+        AlertStatus::Firing => SystemEvent::AlertRaised { id, name, board, message: record.message.clone() },
+        AlertStatus::Acked => SystemEvent::AlertAcked { id, name, board },
+        AlertStatus::Resolved => SystemEvent::AlertResolved { id, name, board },
+    });
+
+    let wanted = state
+        .config
+        .alerts
+        .definitions
+        .iter()
+        .find(|d| d.name == record.name)
+        .map(|d| d.sinks.clone())
+        .unwrap_or_default();
+    for sink in state.config.alerts.sinks.iter().filter(|s| wanted.is_empty() || wanted.iter().any(|w| w == s.name())) {
+        let sink = sink.clone();
+        let record = record.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = sink.notify(&record, &state).await {
+                log::error!("Alert #{} -> sink '{}': {}", record.id, sink.name(), e);
+                state.broadcast(SystemEvent::Error { source: "alerts".to_string(), message: format!("Sink '{}': {}", sink.name(), e) });
+            }
+        });
+    }
+}
@@ -0,0 +1,9 @@
+mod book;
+mod commands;
+mod engine;
+mod sinks;
+
+pub use book::{AlertBook, AlertDef};
+pub use commands::handle_alerts;
+pub use engine::spawn_alerts;
+pub use sinks::SinkConfig;
@@ -0,0 +1,93 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::alerts::book::AlertRecord;
+use crate::state::AppState;
+
+/// Where alert notifications go. Add a variant here to support a new kind of sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SinkConfig {
+    // POSTs the alert record as JSON
+    Webhook { name: String, url: String },
+    Smtp {
+        name: String,
+        host: String,
+        port: u16,
+        from: String,
+        to: Vec<String>,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        // Plain SMTP is fine for a local test server
+        #[serde(default)]
+        starttls: bool,
+    },
+    // Publishes the alert record as JSON through the backend's MQTT client
+    Mqtt { name: String, topic: String },
+}
+
+impl SinkConfig {
+    pub fn name(&self) -> &str {
+        match self {
+            SinkConfig::Webhook { name, .. } | SinkConfig::Smtp { name, .. } | SinkConfig::Mqtt { name, .. } => name,
+        }
+    }
+
+    pub async fn notify(&self, alert: &AlertRecord, state: &AppState) -> Result<(), String> {
+        let body = serde_json::to_string(alert).map_err(|e| e.to_string())?;
+        match self {
+            SinkConfig::Webhook { url, .. } => {
+                let resp = reqwest::Client::new()
+                    .post(url)
+                    .timeout(Duration::from_secs(10))
+                    .header("content-type", "application/json")
+                    .body(body)
+                    .send()
+                    .await
+                    .map_err(|e| format!("Webhook failed: {}", e))?;
+                if !resp.status().is_success() {
+                    return Err(format!("Webhook returned {}", resp.status()));
+                }
+                Ok(())
+            }
+            SinkConfig::Smtp { host, port, from, to, username, password, starttls, .. } => {
+                let from: Mailbox = from.parse().map_err(|e| format!("Bad from address: {}", e))?;
+                let mut builder = Message::builder()
+                    .from(from)
+                    .subject(format!("[Miniverse] {:?}: {} ({})", alert.status, alert.name, alert.board));
+                for addr in to {
+                    builder = builder.to(addr.parse().map_err(|e| format!("Bad to address '{}': {}", addr, e))?);
+                }
+                let email = builder
+                    .body(format!("{}\n\n{}", alert.summary(), body))
+                    .map_err(|e| format!("Build email failed: {}", e))?;
+
+                let mut transport = if *starttls {
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(|e| format!("SMTP setup failed: {}", e))?
+                } else {
+                    AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+                }
+                .port(*port)
+                .timeout(Some(Duration::from_secs(10)));
+                if let (Some(user), Some(pass)) = (username, password) {
+                    transport = transport.credentials(Credentials::new(user.clone(), pass.clone()));
+                }
+                transport
+                    .build()
+                    .send(email)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| format!("SMTP send failed: {}", e))
+            }
+            SinkConfig::Mqtt { topic, .. } => {
+                let mqtt = state.mqtt.read().await;
+                mqtt.publish(topic, body.as_bytes()).await
+            }
+        }
+    }
+}
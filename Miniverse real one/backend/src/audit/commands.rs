@@ -0,0 +1,71 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::auth::{Identity, Role};
+use crate::events::SystemEvent;
+use crate::shell::{Invocation, Spec};
+use crate::state::AppState;
+
+const USAGE: &str = "Usage:\n  audit tail [n] [user]   last n entries (default 20), optionally for one user\n  audit errors [n]        last n failed commands\n";
+
+const TAIL: Spec = Spec::new("audit tail [n] [user]", 0, Some(2));
+const ERRORS: Spec = Spec::new("audit errors [n]", 0, Some(1));
+
+/// `audit tail [n] [user]` and `audit errors [n]`
+pub fn handle_audit(inv: &Invocation, state: &AppState) -> SystemEvent {
+    let sub = inv.subcommand();
+    let (spec, errors_only) = match sub.as_ref().map(|s| s.name()) {
+        None | Some("tail") => (TAIL, false),
+        Some("errors") => (ERRORS, true),
+        Some(other) => {
+            let at = sub.as_ref().map_or_else(String::new, |s| s.error_at_name(&format!("Unknown audit subcommand '{}'", other)));
+            return SystemEvent::Error { source: "cli".to_string(), message: format!("{}\n{}", at, USAGE) };
+        }
+    };
+    let parsed = match &sub {
+        Some(sub) => sub.args(&spec).and_then(|a| Ok((a.parse::<usize>(0, "count")?, a.get(1).map(str::to_string)))),
+        None => Ok((None, None)),
+    };
+    let (limit, user) = match parsed {
+        Ok((limit, user)) => (limit.unwrap_or(20), user),
+        Err(message) => return SystemEvent::Error { source: "cli".to_string(), message },
+    };
+    let entries = state.audit.tail(limit, |e| (!errors_only || !e.ok) && user.as_ref().is_none_or(|u| e.user == *u));
+    if entries.is_empty() {
+        return SystemEvent::Output { content: "No audit entries".to_string() };
+    }
+    let mut out = format!("Audit ({}):\n", entries.len());
+    for entry in &entries {
+        out.push_str(&format!("{}\n", entry.summary()));
+    }
+    SystemEvent::Output { content: out }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    limit: Option<usize>,
+    user: Option<String>,
+    session: Option<u64>,
+    // Only failed (true) or only successful (false) commands
+    ok: Option<bool>,
+}
+
+/// GET /api/audit?limit=&user=&session=&ok=
+pub async fn audit_route(
+    query: web::Query<AuditQuery>,
+    identity: web::ReqData<Identity>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if identity.role < Role::Admin {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("The audit log requires the admin role (you are {})", identity.role)
+        }));
+    }
+    let q = query.into_inner();
+    let entries = state.audit.tail(q.limit.unwrap_or(100), |e| {
+        q.user.as_ref().is_none_or(|u| e.user == *u)
+            && q.session.is_none_or(|s| e.session == Some(s))
+            && q.ok.is_none_or(|ok| e.ok == ok)
+    });
+    HttpResponse::Ok().json(entries)
+}
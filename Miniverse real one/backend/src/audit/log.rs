@@ -0,0 +1,159 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::AuditConfig;
+
+/// One handled `ClientCommand`: who sent it, where it went and how it ended
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(default)]
+    pub id: u64,
+    pub at: DateTime<Local>,
+    pub session: Option<u64>,
+    pub user: String,
+    pub transport: String,
+    // command, mode, subscribe, publish, filter
+    pub kind: String,
+    // Serial port or MQTT topic the command resolved to; empty when it never reaches a device
+    pub target: String,
+    pub payload: String,
+    pub ok: bool,
+    pub result: String,
+}
+
+impl AuditEntry {
+    pub fn summary(&self) -> String {
+        let who = match self.session {
+            Some(s) => format!("{}#{}", self.user, s),
+            None => self.user.clone(),
+        };
+        let target = if self.target.is_empty() { String::new() } else { format!(" -> {}", self.target) };
+        format!(
+            "#{:<5} {} {:<16} {:<6} {} {:?}{} : {}",
+            self.id,
+            self.at.format("%m-%d %H:%M:%S"),
+            who,
+            self.transport,
+            self.kind,
+            self.payload,
+            target,
+            if self.ok { "ok".to_string() } else { format!("ERROR {}", self.result) }
+        )
+    }
+}
+
+/// Append-only JSON-lines file at `AuditConfig::path`, rotated by size,
+/// with the newest entries also kept in memory for queries
+pub struct AuditLog {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    recent: VecDeque<AuditEntry>,
+    next_id: u64,
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    memory: usize,
+}
+
+// Long responses (help, listings) are cut down before they're stored
+const MAX_RESULT: usize = 200;
+
+impl AuditLog {
+    /// Pick up the tail of the current file so ids continue across restarts
+    pub fn open(cfg: &AuditConfig) -> Self {
+        let path = PathBuf::from(&cfg.path);
+        let mut recent: VecDeque<AuditEntry> = std::fs::read_to_string(&path)
+            .map(|text| text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+            .unwrap_or_default();
+        while recent.len() > cfg.memory_entries {
+            recent.pop_front();
+        }
+        let next_id = recent.back().map(|e| e.id + 1).unwrap_or(1);
+        Self {
+            inner: Mutex::new(Inner {
+                recent,
+                next_id,
+                path,
+                max_bytes: cfg.max_bytes,
+                keep: cfg.keep_files,
+                memory: cfg.memory_entries,
+            }),
+        }
+    }
+
+    pub fn record(&self, mut entry: AuditEntry) {
+        let Ok(mut inner) = self.inner.lock() else { return };
+        entry.id = inner.next_id;
+        inner.next_id += 1;
+        if entry.result.len() > MAX_RESULT {
+            let cut = (0..=MAX_RESULT).rev().find(|i| entry.result.is_char_boundary(*i)).unwrap_or(0);
+            entry.result.truncate(cut);
+            entry.result.push_str("...");
+        }
+        if let Err(e) = inner.append(&entry) {
+            log::error!("Audit: {}", e);
+        }
+        inner.recent.push_back(entry);
+        while inner.recent.len() > inner.memory {
+            inner.recent.pop_front();
+        }
+    }
+
+    /// Newest last, at most `limit` entries matching `keep`
+    pub fn tail(&self, limit: usize, keep: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+        let Ok(inner) = self.inner.lock() else { return Vec::new() };
+        let mut out: Vec<AuditEntry> = inner.recent.iter().rev().filter(|e| keep(e)).take(limit).cloned().collect();
+        out.reverse();
+        out
+    }
+}
+
+impl Inner {
+    fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("Create {} failed: {}", dir.display(), e))?;
+            }
+        }
+        if std::fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_bytes) {
+            self.rotate()?;
+        }
+        let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Open {} failed: {}", self.path.display(), e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Write {} failed: {}", self.path.display(), e))
+    }
+
+    // audit.log -> audit.log.1 -> ... -> audit.log.<keep>, dropping the oldest
+    fn rotate(&self) -> Result<(), String> {
+        let numbered = |n: usize| -> PathBuf { PathBuf::from(format!("{}.{}", self.path.display(), n)) };
+        if self.keep == 0 {
+            return remove(&self.path);
+        }
+        remove(&numbered(self.keep))?;
+        for n in (1..self.keep).rev() {
+            let from = numbered(n);
+            if from.exists() {
+                std::fs::rename(&from, numbered(n + 1)).map_err(|e| format!("Rotate {} failed: {}", from.display(), e))?;
+            }
+        }
+        std::fs::rename(&self.path, numbered(1)).map_err(|e| format!("Rotate {} failed: {}", self.path.display(), e))
+    }
+}
+
+fn remove(path: &Path) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Remove {} failed: {}", path.display(), e)),
+        _ => Ok(()),
+    }
+}
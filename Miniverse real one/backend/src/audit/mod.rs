@@ -0,0 +1,5 @@
+mod commands;
+mod log;
+
+pub use commands::{audit_route, handle_audit};
+pub use log::{AuditEntry, AuditLog};
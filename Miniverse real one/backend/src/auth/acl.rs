@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::identity::Identity;
+use crate::auth::Role;
+use crate::events::SystemEvent;
+use crate::mqtt::{filter_covers, filters_overlap};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Publish,
+    Subscribe,
+    #[default]
+    Both,
+}
+
+/// One ACL entry, e.g. `{ "effect": "deny", "topic": "miniverse/+/+/command", "access": "publish" }`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicRule {
+    pub effect: Effect,
+    // MQTT filter; `+` and `#` wildcards allowed
+    pub topic: String,
+    #[serde(default)]
+    pub access: Access,
+}
+
+impl TopicRule {
+    // An allow must cover everything requested; a deny wins on any overlap
+    fn decides(&self, access: Access, topic: &str) -> Option<bool> {
+        if self.access != Access::Both && self.access != access {
+            return None;
+        }
+        match self.effect {
+            Effect::Allow if filter_covers(&self.topic, topic) => Some(true),
+            Effect::Deny if filters_overlap(&self.topic, topic) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Check a publish topic or subscribe filter against the session's own rules, then its role's.
+/// The first rule that applies decides; with no match the topic is allowed, so end a list
+/// with `deny #` for allow-list behaviour.
+pub fn check_topic(identity: &Identity, state: &AppState, access: Access, topic: &str) -> Result<(), String> {
+    let role_rules = state.config.auth.role_acls.get(&identity.role).map(Vec::as_slice).unwrap_or_default();
+    let allowed = identity
+        .acl
+        .iter()
+        .chain(role_rules)
+        .find_map(|rule| rule.decides(access, topic))
+        .unwrap_or(true);
+    if allowed {
+        Ok(())
+    } else {
+        let verb = if access == Access::Publish { "publish to" } else { "subscribe to" };
+        Err(format!("{} may not {} '{}'", identity.user, verb, topic))
+    }
+}
+
+/// Whether a broadcast event may reach `identity`, live or replayed: MQTT traffic on topics
+/// it may not subscribe to and other users' script steps are withheld
+pub fn may_see(identity: &Identity, state: &AppState, event: &SystemEvent) -> bool {
+    match event {
+        SystemEvent::MqttMessage { topic, .. } => check_topic(identity, state, Access::Subscribe, topic).is_ok(),
+        SystemEvent::ScriptStep { user, .. } => *user == identity.user || identity.role >= Role::Admin,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(effect: Effect, topic: &str, access: Access) -> TopicRule {
+        TopicRule { effect, topic: topic.to_string(), access }
+    }
+
+    #[test]
+    fn allow_must_cover_the_whole_request() {
+        let allow = rule(Effect::Allow, "miniverse/b1/#", Access::Both);
+        assert_eq!(allow.decides(Access::Subscribe, "miniverse/b1/temp/state"), Some(true));
+        assert_eq!(allow.decides(Access::Subscribe, "miniverse/b1/#"), Some(true));
+        // Reaches beyond b1, so this rule says nothing
+        assert_eq!(allow.decides(Access::Subscribe, "miniverse/+/temp/state"), None);
+    }
+
+    #[test]
+    fn deny_wins_on_any_overlap() {
+        let deny = rule(Effect::Deny, "miniverse/secret/#", Access::Both);
+        assert_eq!(deny.decides(Access::Subscribe, "miniverse/#"), Some(false));
+        assert_eq!(deny.decides(Access::Publish, "miniverse/secret/door"), Some(false));
+        // `+` can be "secret", so a wildcard level still overlaps
+        assert_eq!(deny.decides(Access::Subscribe, "miniverse/+/temp"), Some(false));
+        assert_eq!(deny.decides(Access::Subscribe, "miniverse/public/temp"), None);
+    }
+
+    #[test]
+    fn rules_apply_to_their_access_only() {
+        let deny = rule(Effect::Deny, "miniverse/+/+/command", Access::Publish);
+        assert_eq!(deny.decides(Access::Publish, "miniverse/b1/led/command"), Some(false));
+        assert_eq!(deny.decides(Access::Subscribe, "miniverse/b1/led/command"), None);
+    }
+}
@@ -0,0 +1,142 @@
+use actix_web::{web, HttpRequest};
+use base64::Engine;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::auth::acl::TopicRule;
+use crate::auth::role::Role;
+use crate::state::AppState;
+
+/// Who is behind a request or WebSocket session
+#[derive(Debug, Clone, Serialize)]
+pub struct Identity {
+    pub user: String,
+    // "token", "password", "login" or "none" when auth is disabled
+    pub method: &'static str,
+    pub role: Role,
+    // Per-token or per-user topic rules, checked before the role's
+    #[serde(skip)]
+    pub acl: Vec<TopicRule>,
+    // Set for WebSocket sessions; owns per-session state such as MQTT subscriptions
+    #[serde(skip)]
+    pub session: Option<u64>,
+}
+
+impl Identity {
+    // Without auth everyone keeps full access, as before auth existed
+    pub fn anonymous() -> Self {
+        Self { user: "anonymous".to_string(), method: "none", role: Role::Admin, acl: Vec::new(), session: None }
+    }
+
+    /// The backend itself: scheduled jobs, rules and alerts
+    pub fn system() -> Self {
+        Self { user: "system".to_string(), method: "internal", role: Role::Admin, acl: Vec::new(), session: None }
+    }
+
+    pub fn describe(&self) -> String {
+        let how = match self.method {
+            "none" => "authentication disabled",
+            "token" => "API token",
+            "password" => "password",
+            "internal" => "internal",
+            _ => "login session",
+        };
+        format!("{} [{}] ({})", self.user, self.role, how)
+    }
+
+    /// `Err` with a user-facing message when this session lacks `needed`
+    pub fn allows(&self, needed: Role, what: &str) -> Result<(), String> {
+        if self.role >= needed {
+            Ok(())
+        } else {
+            Err(format!("'{}' requires the {} role (you are {})", what, needed, self.role))
+        }
+    }
+}
+
+/// A `POST /api/login` session token's owner, until it expires or is revoked
+#[derive(Debug, Clone)]
+pub struct Login {
+    pub identity: Identity,
+    pub expires: DateTime<Local>,
+}
+
+/// Check `Authorization: Bearer <token>`, `Authorization: Basic <user:pass>` or `?token=<token>`
+pub async fn authenticate(req: &HttpRequest, state: &AppState) -> Result<Identity, String> {
+    let cfg = &state.config.auth;
+    if !cfg.enabled {
+        return Ok(Identity::anonymous());
+    }
+
+    if let Some(header) = req.headers().get("authorization") {
+        let header = header.to_str().map_err(|_| "Malformed Authorization header".to_string())?;
+        let (scheme, value) = header.split_once(' ').ok_or("Malformed Authorization header")?;
+        return match scheme.to_ascii_lowercase().as_str() {
+            "bearer" => check_token(value.trim(), state).await,
+            "basic" => {
+                let decoded = base64::engine::general_purpose::STANDARD
+                    .decode(value.trim())
+                    .ok()
+                    .and_then(|b| String::from_utf8(b).ok())
+                    .ok_or("Malformed Basic credentials")?;
+                let (user, pass) = decoded.split_once(':').ok_or("Malformed Basic credentials")?;
+                check_password(user, pass, state)
+            }
+            other => Err(format!("Unsupported auth scheme '{}'", other)),
+        };
+    }
+
+    match query_token(req) {
+        Some(token) => check_token(&token, state).await,
+        None => Err("Missing credentials: use an Authorization header or ?token=".to_string()),
+    }
+}
+
+/// The bearer token a request authenticated with, from the header or `?token=`
+pub fn presented_token(req: &HttpRequest) -> Option<String> {
+    match req.headers().get("authorization").and_then(|h| h.to_str().ok()) {
+        Some(header) => header.split_once(' ').filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer")).map(|(_, t)| t.trim().to_string()),
+        None => query_token(req),
+    }
+}
+
+// URL-decoded; browsers can only authenticate WebSocket and EventSource requests this way
+fn query_token(req: &HttpRequest) -> Option<String> {
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|q| q.get("token").cloned())
+        .filter(|t| !t.is_empty())
+}
+
+async fn check_token(token: &str, state: &AppState) -> Result<Identity, String> {
+    if let Some(t) = state.config.auth.tokens.iter().find(|t| same(&t.token, token)) {
+        return Ok(Identity { user: t.name.clone(), method: "token", role: t.role, acl: t.acl.clone(), session: None });
+    }
+    let mut logins = state.logins.write().await;
+    match logins.get(token) {
+        Some(login) if login.expires > Local::now() => Ok(Identity { method: "login", ..login.identity.clone() }),
+        Some(_) => {
+            logins.remove(token);
+            Err("Login expired; log in again".to_string())
+        }
+        None => Err("Invalid token".to_string()),
+    }
+}
+
+/// Username/password check used by Basic auth and `POST /api/login`
+pub(super) fn check_password(user: &str, pass: &str, state: &AppState) -> Result<Identity, String> {
+    state
+        .config
+        .auth
+        .users
+        .iter()
+        .find(|u| u.username == user && same(&u.password, pass))
+        .map(|u| Identity { user: u.username.clone(), method: "password", role: u.role, acl: u.acl.clone(), session: None })
+        .ok_or_else(|| "Invalid username or password".to_string())
+}
+
+// Compare without an early exit so response time doesn't leak how much matched
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
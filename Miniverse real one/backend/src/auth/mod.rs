@@ -0,0 +1,9 @@
+mod acl;
+mod identity;
+mod role;
+mod routes;
+
+pub use acl::{check_topic, may_see, Access, TopicRule};
+pub use identity::{authenticate, Identity, Login};
+pub use role::{command_role, required_role, Role};
+pub use routes::{login_route, logout_route, require_auth, unauthorized};
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::events::ClientCommand;
+use crate::shell::Invocation;
+
+/// Session roles, ordered by privilege
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // Read-only: status, sensor reads, listings
+    #[default]
+    Viewer,
+    // Drives devices: LEDs, LCD, MQTT publish/subscribe, scripts, acking alerts
+    Operator,
+    // Shared setup: serial port, transport, persisted schedules and rules
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+/// Role a session needs to send this command
+pub fn required_role(cmd: &ClientCommand) -> Role {
+    match cmd {
+        ClientCommand::Command { command } => command_role(command),
+        ClientCommand::ChangeMode { mode } if mode.eq_ignore_ascii_case("config") => Role::Operator,
+        ClientCommand::ChangeMode { .. } => Role::Viewer,
+        ClientCommand::Subscribe { .. } | ClientCommand::Publish { .. } => Role::Operator,
+        ClientCommand::SetFilter { .. } | ClientCommand::ClearFilter | ClientCommand::Complete { .. } => Role::Viewer,
+    }
+}
+
+/// Role for a terminal command line, by command family
+pub fn command_role(command: &str) -> Role {
+    // Lines that don't tokenize never run; the dispatcher reports the syntax error
+    let Ok(inv) = Invocation::parse(command) else { return Role::Viewer };
+    let sub = inv.subcommand();
+    let sub = sub.as_ref().map_or("", |s| s.name());
+    match inv.name() {
+        "help" | "status" | "ports" | "scan" | "info" | "sysinfo" | "./info" | "about" | "version" | "whoami" | "who" | "nick" | "history" | "temp" | "distance" => Role::Viewer,
+        // Matches `ChangeMode`: entering config needs operator, leaving it doesn't
+        "config" => Role::Operator,
+        "exit" | "normal" | "end" => Role::Viewer,
+        // Serial port and transport are shared by every session
+        "connect" | "disconnect" | "transport" => Role::Admin,
+        // Shows what every user did
+        "audit" => Role::Admin,
+        "light" | "led" | "set" | "lcd" => Role::Operator,
+        // `unlock force` additionally checks for admin
+        "lock" | "unlock" => Role::Operator,
+        "mqtt" => match sub {
+            "" | "subs" | "list" | "ls" => Role::Viewer,
+            _ => Role::Operator,
+        },
+        "schedule" | "rule" | "rules" => match sub {
+            "" | "list" | "ls" => Role::Viewer,
+            _ => Role::Admin,
+        },
+        "alerts" | "alert" => match sub {
+            "ack" | "resolve" => Role::Operator,
+            _ => Role::Viewer,
+        },
+        "unwatch" => Role::Viewer,
+        // A watch needs whatever its polled command needs
+        "watch" => match inv.subcommand() {
+            Some(interval) if !interval.tail().is_empty() => command_role(interval.tail()),
+            _ => Role::Viewer,
+        },
+        // At least operator; the runner checks the steps against the script text it runs
+        "run" => Role::Operator,
+        // Unknown commands are rejected by the dispatcher
+        _ => Role::Viewer,
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::auth::identity::{authenticate, check_password, presented_token, Login};
+use crate::state::AppState;
+
+pub fn unauthorized(message: &str) -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header(("WWW-Authenticate", "Bearer realm=\"miniverse\""))
+        .json(serde_json::json!({ "error": message }))
+}
+
+/// Middleware for the `/api` scope: rejects with 401 or stores the `Identity` in request extensions
+pub async fn require_auth(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return next.call(req).await.map(|res| res.map_into_left_body());
+    };
+    match authenticate(req.request(), &state).await {
+        Ok(identity) => {
+            req.extensions_mut().insert(identity);
+            next.call(req).await.map(|res| res.map_into_left_body())
+        }
+        Err(e) => {
+            log::warn!("Rejected {} {}: {}", req.method(), req.path(), e);
+            Ok(req.into_response(unauthorized(&e)).map_into_right_body())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+/// `POST /api/login` exchanges a username/password for a session token usable as `?token=` on `/ws`
+pub async fn login_route(body: web::Json<LoginRequest>, state: web::Data<AppState>) -> HttpResponse {
+    if !state.config.auth.enabled {
+        return HttpResponse::Ok().json(serde_json::json!({ "user": "anonymous", "role": "admin", "token": null }));
+    }
+    match check_password(&body.username, &body.password, &state) {
+        Ok(identity) => {
+            let token = uuid::Uuid::new_v4().simple().to_string();
+            let expires = chrono::Local::now() + chrono::Duration::seconds(state.config.auth.login_ttl_secs as i64);
+            log::info!("Login: {} [{}]", identity.user, identity.role);
+            let body = serde_json::json!({ "user": identity.user, "role": identity.role, "token": token, "expires": expires });
+            let mut logins = state.logins.write().await;
+            // Drop expired tokens so the table doesn't grow with every login
+            let now = chrono::Local::now();
+            logins.retain(|_, l| l.expires > now);
+            logins.insert(token, Login { identity, expires });
+            HttpResponse::Ok().json(body)
+        }
+        Err(e) => unauthorized(&e),
+    }
+}
+
+/// `POST /api/logout` revokes the login token the request was made with
+pub async fn logout_route(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    let Some(token) = presented_token(&req) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Log out with the login token itself" }));
+    };
+    match state.logins.write().await.remove(&token) {
+        Some(login) => {
+            log::info!("Logout: {}", login.identity.user);
+            HttpResponse::NoContent().finish()
+        }
+        None => HttpResponse::BadRequest().json(serde_json::json!({ "error": "Not a login token; configured API tokens can't be revoked" })),
+    }
+}
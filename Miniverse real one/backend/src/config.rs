@@ -1,12 +1,31 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::alerts::{AlertDef, SinkConfig};
+use crate::auth::{Role, TopicRule};
+use crate::rules::Rule;
+use crate::scheduler::Job;
+
+// Every section has its own defaults, so a config file only needs the fields it changes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub mqtt: MqttConfig,
     pub server: ServerConfig,
+    pub serial: SerialConfig,
+    pub scripts: ScriptsConfig,
+    pub watch: WatchConfig,
+    pub scheduler: SchedulerConfig,
+    pub rules: RulesConfig,
+    pub alerts: AlertsConfig,
+    pub auth: AuthConfig,
+    pub events: EventsConfig,
+    pub audit: AuditConfig,
+    pub history: HistoryConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MqttConfig {
     pub broker_host: String,
     pub broker_port: u16,
@@ -15,31 +34,263 @@ pub struct MqttConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub cors_origins: Vec<String>,
 }
 
-impl Default for Config {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialConfig {
+    pub default_baud_rate: u32,
+    // Rates tried in order by `connect <n> auto`
+    pub auto_baud_rates: Vec<u32>,
+    pub probe_command: String,
+    pub probe_timeout_ms: u64,
+    // Per-query wait for VERSION / INFO / HELP replies on connect
+    pub handshake_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptsConfig {
+    // Where `run <name>` looks for `<name>` or `<name>.mv`
+    pub dir: String,
+    // Upper bound on steps after `repeat` expansion
+    pub max_steps: usize,
+    // Upper bound on the total time one script sleeps
+    pub max_sleep_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    // Shortest interval `watch` accepts
+    pub min_interval_ms: u64,
+    pub max_per_session: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    // JSON file holding the job table; created on first change
+    pub store_path: String,
+    // Seed jobs used when no store file exists yet
+    pub jobs: Vec<Job>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulesConfig {
+    // JSON file holding the rule table; created on first change
+    pub store_path: String,
+    // Seed rules used when no store file exists yet
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    // How often silent-board conditions are checked
+    pub check_interval_ms: u64,
+    pub definitions: Vec<AlertDef>,
+    pub sinks: Vec<SinkConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    // Off by default so a bench setup works without credentials
+    pub enabled: bool,
+    pub tokens: Vec<ApiToken>,
+    pub users: Vec<UserAccount>,
+    // MQTT topic rules per role, checked after a token's or user's own `acl`
+    #[serde(default)]
+    pub role_acls: HashMap<Role, Vec<TopicRule>>,
+    // How long a POST /api/login token stays valid
+    pub login_ttl_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    // Shown by `whoami` for sessions using this token
+    pub name: String,
+    pub token: String,
+    // Defaults to viewer
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub acl: Vec<TopicRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserAccount {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub acl: Vec<TopicRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventsConfig {
+    // Events buffered per client before a slow one starts losing them
+    pub channel_capacity: usize,
+    // Newest events kept for SSE clients resuming with Last-Event-ID; 0 turns replay off.
+    // Capped at sse::MAX_REPLAY_SIZE.
+    pub replay_size: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    // JSON lines, rotated to <path>.1 .. <path>.<keep_files> once it reaches max_bytes
+    pub path: String,
+    pub max_bytes: u64,
+    pub keep_files: usize,
+    // Newest entries kept in memory for `audit tail` and GET /api/audit
+    pub memory_entries: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    // JSON file with every user's command lines; created on first command
+    pub store_path: String,
+    pub max_per_user: usize,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker_host: "localhost".to_string(),
+            broker_port: 1883,
+            client_id: "miniverse-backend".to_string(),
+            default_topics: vec!["miniverse/#".to_string()],
+        }
+    }
+}
+
+impl Default for ServerConfig {
     fn default() -> Self {
         Self {
-            mqtt: MqttConfig {
-                broker_host: "localhost".to_string(),
-                broker_port: 1883,
-                client_id: "miniverse-backend".to_string(),
-                default_topics: vec!["miniverse/#".to_string()],
-            },
-            server: ServerConfig {
-                host: "127.0.0.1".to_string(),
-                port: 8080,
-                cors_origins: vec!["http://localhost:4321".to_string()],
-            },
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            cors_origins: vec!["http://localhost:4321".to_string()],
+        }
+    }
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            default_baud_rate: 115200,
+            auto_baud_rates: vec![115200, 9600, 57600, 38400, 19200],
+            probe_command: "VERSION".to_string(),
+            probe_timeout_ms: 2500,
+            handshake_timeout_ms: 1500,
+        }
+    }
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            dir: "scripts".to_string(),
+            max_steps: 1000,
+            max_sleep_ms: 600_000,
+        }
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            min_interval_ms: 500,
+            max_per_session: 4,
+        }
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            store_path: "data/schedule.json".to_string(),
+            jobs: Vec::new(),
+        }
+    }
+}
+
+impl Default for RulesConfig {
+    fn default() -> Self {
+        Self {
+            store_path: "data/rules.json".to_string(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_ms: 1000,
+            definitions: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tokens: Vec::new(),
+            users: Vec::new(),
+            role_acls: HashMap::new(),
+            login_ttl_secs: 12 * 3600,
+        }
+    }
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            channel_capacity: 100,
+            replay_size: 200,
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: "data/audit.log".to_string(),
+            max_bytes: 1_000_000,
+            keep_files: 5,
+            memory_entries: 1000,
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            store_path: "data/history.json".to_string(),
+            max_per_user: 500,
         }
     }
 }
 
 impl Config {
-    #[allow(dead_code)]
-    pub fn from_env() -> Self { Self::default() }
+    /// Defaults, overridden field by field by the JSON file at `MINIVERSE_CONFIG` if set.
+    /// A file that can't be read or parsed is an error: falling back to defaults would turn auth off.
+    pub fn from_env() -> Result<Self, String> {
+        let Ok(path) = std::env::var("MINIVERSE_CONFIG") else { return Ok(Self::default()) };
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("Config: cannot read {}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Config: cannot parse {}: {}", path, e))
+    }
 }
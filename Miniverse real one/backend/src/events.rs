@@ -1,6 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Version of the event and command schema served at `/api/schema`, announced in `connected`.
+/// The major part changes only when existing clients would break.
+pub const PROTOCOL_VERSION: &str = "2.0.0";
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum SystemEvent {
     #[serde(rename = "mqtt_message")]
@@ -12,6 +17,9 @@ pub enum SystemEvent {
         port: Option<String>,
         baud_rate: Option<u32>,
         board_name: Option<String>,
+        // True when baud_rate was picked by `connect <n> auto`
+        #[serde(default)]
+        auto_baud: bool,
     },
     
     #[serde(rename = "sensor_info")]
@@ -19,6 +27,9 @@ pub enum SystemEvent {
         sensors: Vec<SensorDetail>,
         board: String,
         firmware: String,
+        // Command prefixes advertised by the firmware's CMDS: line
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        capabilities: Vec<String>,
     },
     
     #[serde(rename = "output")]
@@ -27,12 +38,39 @@ pub enum SystemEvent {
     #[serde(rename = "error")]
     Error { source: String, message: String },
     
+    // Sent to a new client only, with its own session id
     #[serde(rename = "connected")]
-    Connected,
+    Connected {
+        session: u64,
+        user: String,
+        protocol: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nickname: Option<String>,
+    },
+
+    #[serde(rename = "joined")]
+    Joined {
+        session: u64,
+        user: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nickname: Option<String>,
+    },
+
+    #[serde(rename = "left")]
+    Left {
+        session: u64,
+        user: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nickname: Option<String>,
+    },
     
     #[serde(rename = "mode_changed")]
     ModeChanged { mode: String },
 
+    // Answer to `complete`: each item is a whole replacement line
+    #[serde(rename = "completions")]
+    Completions { partial: String, items: Vec<String> },
+
     #[serde(rename = "transport_changed")]
     TransportChanged {
         transport: String,
@@ -43,17 +81,94 @@ pub enum SystemEvent {
         board_id: Option<String>,
     },
 
-    
+    // Sent to one client that fell behind the event channel
+    #[serde(rename = "events_dropped")]
+    EventsDropped { count: u64 },
+
+    #[serde(rename = "script_step")]
+    ScriptStep {
+        script: String,
+        // Who ran the script; only they and admins see its steps
+        user: String,
+        line: usize,
+        command: String,
+        ok: bool,
+        output: String,
+    },
+
+    #[serde(rename = "script_finished")]
+    ScriptFinished {
+        script: String,
+        executed: usize,
+        failed: usize,
+        stopped: bool,
+    },
+
+    #[serde(rename = "reading")]
+    Reading {
+        board: String,
+        sensor: String,
+        value: f64,
+        unit: String,
+    },
+
+    #[serde(rename = "rule_fired")]
+    RuleFired {
+        id: u32,
+        rule: String,
+        trigger: String,
+        ok: bool,
+        output: String,
+    },
+
+    #[serde(rename = "job_run")]
+    JobRun {
+        id: u32,
+        action: String,
+        ok: bool,
+        output: String,
+    },
+
+    #[serde(rename = "alert_raised")]
+    AlertRaised {
+        id: u32,
+        name: String,
+        board: String,
+        message: String,
+    },
+
+    #[serde(rename = "alert_acked")]
+    AlertAcked {
+        id: u32,
+        name: String,
+        board: String,
+    },
+
+    #[serde(rename = "alert_resolved")]
+    AlertResolved {
+        id: u32,
+        name: String,
+        board: String,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SensorDetail {
     pub id: u8,
     pub name: String,
     pub pin: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A `ClientCommand` with an optional id. The direct reply echoes it, followed by a `done` marker.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ClientRequest {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum ClientCommand {
     #[serde(rename = "command")]
@@ -67,4 +182,22 @@ pub enum ClientCommand {
     
     #[serde(rename = "publish")]
     Publish { topic: String, payload: String },
+
+    // Replace this session's event filter; empty lists leave that dimension open
+    #[serde(rename = "set_filter")]
+    SetFilter {
+        #[serde(default)]
+        topics: Vec<String>,
+        #[serde(default)]
+        boards: Vec<String>,
+        #[serde(default)]
+        events: Vec<String>,
+    },
+
+    #[serde(rename = "clear_filter")]
+    ClearFilter,
+
+    // Tab completion for a partly typed terminal line
+    #[serde(rename = "complete")]
+    Complete { partial: String },
 }
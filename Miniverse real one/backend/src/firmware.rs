@@ -0,0 +1,146 @@
+use crate::events::{SensorDetail, SystemEvent};
+use crate::serial::SerialBridge;
+
+/// Queries sent to the board on connect, paired with the line prefix that ends each reply
+pub const HANDSHAKE_QUERIES: &[(&str, &str)] = &[
+    ("VERSION", "VERSION:"),
+    ("INFO", "FIRMWARE:"),
+    ("HELP", "CMDS:"),
+];
+
+/// What a board told us about itself via VERSION / INFO / HELP
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareInfo {
+    pub version: Option<String>,
+    pub board: Option<String>,
+    // Board id used in the board's MQTT topics, from ID:
+    pub id: Option<String>,
+    pub firmware: Option<String>,
+    pub sensors: Vec<SensorDetail>,
+    // Upper-cased command prefixes from the CMDS: list; empty means "not advertised"
+    pub commands: Vec<String>,
+    // Set once the missing queries have been requested over MQTT, to avoid re-asking on every info/state
+    pub queried: bool,
+}
+
+impl FirmwareInfo {
+    /// Apply a single reply line such as `VERSION:1.0.1` or `CMDS: TEMP, DISTANCE`
+    pub fn apply_line(&mut self, line: &str) {
+        let line = line.trim();
+        if let Some(v) = line.strip_prefix("VERSION:") {
+            self.version = Some(v.trim().to_string());
+        } else if let Some(b) = line.strip_prefix("BOARD:") {
+            self.board = Some(b.trim().to_string());
+        } else if let Some(i) = line.strip_prefix("ID:") {
+            self.id = Some(i.trim().to_string()).filter(|i| !i.is_empty());
+        } else if let Some(f) = line.strip_prefix("FIRMWARE:") {
+            self.firmware = Some(f.trim().to_string());
+        } else if let Some(s) = line.strip_prefix("SENSORS:") {
+            self.sensors = parse_sensors(s);
+        } else if let Some(c) = line.strip_prefix("CMDS:") {
+            self.commands = parse_capabilities(c);
+        }
+    }
+
+    /// Apply a compact MQTT payload: `SENSORS:...;BOARD:...;FIRMWARE:...`
+    pub fn apply_payload(&mut self, payload: &str) {
+        for seg in payload.split(';') {
+            self.apply_line(seg);
+        }
+    }
+
+    /// Firmware label for display: FIRMWARE: wins, VERSION: is the fallback
+    pub fn firmware_label(&self) -> String {
+        self.firmware
+            .clone()
+            .or_else(|| self.version.clone())
+            .unwrap_or_else(|| "Unknown".to_string())
+    }
+
+    /// SensorInfo event for this board; `fallback_board` is used when BOARD: was never reported
+    pub fn to_event(&self, fallback_board: &str) -> SystemEvent {
+        SystemEvent::SensorInfo {
+            sensors: self.sensors.clone(),
+            board: self.board.clone().unwrap_or_else(|| fallback_board.to_string()),
+            firmware: self.firmware_label(),
+            capabilities: self.commands.clone(),
+        }
+    }
+
+    /// True when the board answered at least one handshake query
+    pub fn is_known(&self) -> bool {
+        self.version.is_some() || self.firmware.is_some() || !self.sensors.is_empty() || !self.commands.is_empty()
+    }
+
+    /// Handshake queries whose answers we have not seen yet
+    pub fn missing_queries(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.version.is_none() { missing.push("VERSION"); }
+        if self.firmware.is_none() && self.sensors.is_empty() { missing.push("INFO"); }
+        if self.commands.is_empty() { missing.push("HELP"); }
+        missing
+    }
+
+    /// Whether the firmware advertises `payload`. Boards that never sent a CMDS: list are trusted.
+    pub fn supports(&self, payload: &str) -> bool {
+        if self.commands.is_empty() {
+            return true;
+        }
+        let up = payload.trim().to_uppercase();
+        self.commands
+            .iter()
+            .any(|c| up == *c || up.starts_with(&format!("{} ", c)))
+    }
+}
+
+/// Parse `TYPE:PIN,TYPE:PIN` into numbered sensor entries
+pub fn parse_sensors(list: &str) -> Vec<SensorDetail> {
+    list.split(',')
+        .enumerate()
+        .filter_map(|(i, part)| {
+            let kv: Vec<&str> = part.split(':').collect();
+            if kv.len() == 2 {
+                Some(SensorDetail { id: (i + 1) as u8, name: kv[0].trim().to_string(), pin: format!("Pin {}", kv[1].trim()) })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Turn `TEMP, LIGHT ON|OFF|TOGGLE, SET LIGHT <0-255>, LCD SHOW "a" ["b"]` into
+/// command prefixes: argument placeholders are dropped and `A|B` alternatives expanded.
+pub fn parse_capabilities(list: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for entry in list.split(',') {
+        let words: Vec<&str> = entry
+            .split_whitespace()
+            .take_while(|w| !w.starts_with(['<', '[', '"']))
+            .collect();
+        let mut prefixes = vec![String::new()];
+        for word in words {
+            let alts: Vec<&str> = word.split('|').filter(|a| !a.is_empty()).collect();
+            prefixes = prefixes
+                .iter()
+                .flat_map(|p| alts.iter().map(move |a| if p.is_empty() { a.to_uppercase() } else { format!("{} {}", p, a.to_uppercase()) }))
+                .collect();
+        }
+        for p in prefixes {
+            if !p.is_empty() && !out.contains(&p) {
+                out.push(p);
+            }
+        }
+    }
+    out
+}
+
+/// Run VERSION / INFO / HELP against an open serial port and collect the answers
+pub fn handshake(serial: &SerialBridge, timeout_ms: u64) -> FirmwareInfo {
+    let mut info = FirmwareInfo::default();
+    for (query, last) in HANDSHAKE_QUERIES {
+        for line in serial.query(query, |l| l.starts_with(last), timeout_ms) {
+            info.apply_line(&line);
+        }
+    }
+    info
+}
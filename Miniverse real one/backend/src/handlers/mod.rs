@@ -1,2 +0,0 @@
-pub mod websocket;
-pub mod api;
@@ -0,0 +1,189 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+
+use crate::auth::{Identity, Role};
+use crate::config::HistoryConfig;
+use crate::events::SystemEvent;
+use crate::shell::{Invocation, Spec};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // Per-user, keeps counting after old entries are trimmed
+    pub n: u64,
+    pub at: DateTime<Local>,
+    pub command: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UserHistory {
+    next: u64,
+    entries: VecDeque<HistoryEntry>,
+}
+
+/// Terminal command lines per user, persisted as JSON at `HistoryConfig::store_path`
+#[derive(Debug, Default)]
+pub struct History {
+    users: BTreeMap<String, UserHistory>,
+    max_per_user: usize,
+    path: PathBuf,
+}
+
+impl History {
+    pub fn load(cfg: &HistoryConfig) -> Self {
+        let path = PathBuf::from(&cfg.store_path);
+        let users: BTreeMap<String, UserHistory> = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::error!("History: cannot parse {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self { users, max_per_user: cfg.max_per_user, path }
+    }
+
+    pub fn push(&mut self, user: &str, command: &str) -> Result<(), String> {
+        let h = self.users.entry(user.to_string()).or_default();
+        h.next = h.next.max(1);
+        h.entries.push_back(HistoryEntry { n: h.next, at: Local::now(), command: command.to_string() });
+        h.next += 1;
+        while h.entries.len() > self.max_per_user {
+            h.entries.pop_front();
+        }
+        self.save()
+    }
+
+    /// Newest last
+    pub fn recent(&self, user: &str, limit: usize) -> Vec<HistoryEntry> {
+        let Some(h) = self.users.get(user) else { return Vec::new() };
+        h.entries.iter().skip(h.entries.len().saturating_sub(limit)).cloned().collect()
+    }
+
+    pub fn clear(&mut self, user: &str) -> Result<(), String> {
+        if let Some(h) = self.users.get_mut(user) {
+            h.entries.clear();
+        }
+        self.save()
+    }
+
+    /// `!!` (last), `!n` (entry n) or `!-k` (k-th from last); `None` for anything else
+    pub fn expand(&self, user: &str, line: &str) -> Option<Result<String, String>> {
+        let spec = line.trim().strip_prefix('!')?;
+        let entries = self.users.get(user).map(|h| &h.entries);
+        let found = match spec {
+            "!" => entries.and_then(|e| e.back()),
+            _ => match spec.parse::<i64>() {
+                Ok(k) if k < 0 => entries.and_then(|e| e.len().checked_sub(k.unsigned_abs() as usize).and_then(|i| e.get(i))),
+                Ok(n) => entries.and_then(|e| e.iter().find(|h| h.n == n as u64)),
+                Err(_) => return Some(Err("Usage: !! | !<n> | !-<k> (see 'history')".to_string())),
+            },
+        };
+        Some(found.map(|h| h.command.clone()).ok_or(format!("!{}: event not found", spec)))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("Create {} failed: {}", dir.display(), e))?;
+            }
+        }
+        let text = serde_json::to_string(&self.users).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, text).map_err(|e| format!("Write {} failed: {}", self.path.display(), e))
+    }
+}
+
+const HISTORY: Spec = Spec::new("history [n] | history clear; recall with !n, !-k or !!", 0, Some(1));
+
+/// `history [n]` and `history clear`, for the calling user
+pub fn handle_history(inv: &Invocation, state: &AppState, identity: &Identity) -> SystemEvent {
+    let args = match inv.args(&HISTORY) {
+        Ok(a) => a,
+        Err(message) => return SystemEvent::Error { source: "cli".to_string(), message },
+    };
+    let Ok(mut history) = state.history.write() else {
+        return SystemEvent::Error { source: "history".to_string(), message: "History unavailable".to_string() };
+    };
+    match args.get(0) {
+        Some("clear") => match history.clear(&identity.user) {
+            Ok(()) => SystemEvent::Output { content: "History cleared".to_string() },
+            Err(e) => SystemEvent::Error { source: "history".to_string(), message: e },
+        },
+        _ => {
+            let limit = match args.parse::<usize>(0, "count") {
+                Ok(n) => n.unwrap_or(20),
+                Err(message) => return SystemEvent::Error { source: "cli".to_string(), message },
+            };
+            let entries = history.recent(&identity.user, limit);
+            if entries.is_empty() {
+                return SystemEvent::Output { content: "No history".to_string() };
+            }
+            let lines: Vec<String> = entries.iter().map(|e| format!("{:>5}  {}", e.n, e.command)).collect();
+            SystemEvent::Output { content: lines.join("\n") }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    limit: Option<usize>,
+    // Someone else's history; admin only
+    user: Option<String>,
+}
+
+/// GET /api/history?limit=&user=
+pub async fn history_route(
+    query: web::Query<HistoryQuery>,
+    identity: web::ReqData<Identity>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let q = query.into_inner();
+    let user = q.user.unwrap_or_else(|| identity.user.clone());
+    if user != identity.user && identity.role < Role::Admin {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Reading another user's history requires the admin role (you are {})", identity.role)
+        }));
+    }
+    let entries = state.history.read().map(|h| h.recent(&user, q.limit.unwrap_or(100))).unwrap_or_default();
+    HttpResponse::Ok().json(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(max_per_user: usize) -> History {
+        let path = std::env::temp_dir().join(format!("miniverse-history-{}-{}.json", std::process::id(), max_per_user));
+        let _ = std::fs::remove_file(&path);
+        History::load(&HistoryConfig { store_path: path.to_string_lossy().into_owned(), max_per_user })
+    }
+
+    #[test]
+    fn recall_by_number_offset_and_last() {
+        let mut h = history(10);
+        for cmd in ["status", "temp", "light on"] {
+            h.push("ann", cmd).unwrap();
+        }
+        assert_eq!(h.expand("ann", "!!"), Some(Ok("light on".to_string())));
+        assert_eq!(h.expand("ann", "!1"), Some(Ok("status".to_string())));
+        assert_eq!(h.expand("ann", "!-2"), Some(Ok("temp".to_string())));
+        assert!(matches!(h.expand("ann", "!9"), Some(Err(_))));
+        assert!(matches!(h.expand("ann", "!x"), Some(Err(_))));
+        assert!(matches!(h.expand("bob", "!!"), Some(Err(_))));
+        assert_eq!(h.expand("ann", "status"), None);
+    }
+
+    #[test]
+    fn numbers_keep_counting_after_trimming() {
+        let mut h = history(2);
+        for cmd in ["a", "b", "c"] {
+            h.push("ann", cmd).unwrap();
+        }
+        let recent: Vec<(u64, String)> = h.recent("ann", 10).into_iter().map(|e| (e.n, e.command)).collect();
+        assert_eq!(recent, vec![(2, "b".to_string()), (3, "c".to_string())]);
+        assert!(matches!(h.expand("ann", "!1"), Some(Err(_))));
+        assert_eq!(h.expand("ann", "!3"), Some(Ok("c".to_string())));
+    }
+}
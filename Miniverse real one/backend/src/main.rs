@@ -1,16 +1,31 @@
 use actix_cors::Cors;
 use actix_files::Files;
+use actix_web::middleware::from_fn;
 use actix_web::{web, App, HttpResponse, HttpServer};
 
+mod actions;
+mod alerts;
+mod audit;
+mod auth;
 mod config;
 mod events;
+mod firmware;
+mod history;
 mod mqtt;
+mod readings;
+mod rules;
+mod schema;
+mod scheduler;
+mod scripts;
 mod serial;
+mod sessions;
+mod sse;
+mod shell;
 mod state;
 mod websocket;
 
 use config::Config;
-use events::{SystemEvent, SensorDetail};
+use events::SystemEvent;
 use mqtt::MqttManager;
 use serial::SerialBridge;
 use state::AppState;
@@ -20,22 +35,21 @@ async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
 
     log::info!("=== Miniverse Backend Starting ===");
+    // Before anything starts: a broken config must not fall back to defaults
+    let config = Config::from_env().map_err(|e| {
+        log::error!("{}", e);
+        std::io::Error::other(e)
+    })?;
 
     log::info!("Initializing MQTT manager...");
     let (mqtt, mut event_loop) = MqttManager::new("localhost", 1883, "miniverse-backend");
 
-    log::info!("Subscribing to MQTT topic: miniverse/#");
-    if let Err(e) = mqtt.subscribe("miniverse/#").await {
-        log::error!("MQTT subscribe failed: {}", e);
-    }
-
     log::info!("Initializing serial bridge...");
     let serial = SerialBridge::new();
 
     log::info!("Creating application state...");
-    let config = Config::default();
     let state = web::Data::new(AppState::new(config, mqtt, serial));
-    // Initialize default MQTT topics list in state
+    log::info!("Subscribing to default MQTT topics...");
     state.get_ref().init_defaults().await;
 
     log::info!("Starting MQTT listener in separate thread...");
@@ -52,34 +66,53 @@ async fn main() -> std::io::Result<()> {
                     if let Some(caps) = topic.strip_prefix("miniverse/") {
                         let parts: Vec<&str> = caps.split('/').collect();
                         if parts.len() >= 3 && parts[1] == "info" && parts[2] == "state" {
-                            // Expected payload format:
-                            //   SENSORS:TYPE:PIN,TYPE:PIN;BOARD:Name;FIRMWARE:Ver
-                            let mut board = String::from("Unknown");
-                            let mut firmware = String::from("Unknown");
-                            let mut sensors: Vec<SensorDetail> = Vec::new();
-                            for seg in payload.split(';') {
-                                if let Some(s) = seg.strip_prefix("SENSORS:") {
-                                    for (i, sp) in s.split(',').enumerate() {
-                                        let kv: Vec<&str> = sp.split(':').collect();
-                                        if kv.len() == 2 {
-                                            sensors.push(SensorDetail {
-                                                id: (i + 1) as u8,
-                                                name: kv[0].trim().to_string(),
-                                                pin: format!("Pin {}", kv[1].trim()),
-                                            });
+                            // Expected payload formats:
+                            //   SENSORS:TYPE:PIN,TYPE:PIN;BOARD:Name;ID:board_id;FIRMWARE:Ver
+                            //   VERSION:Ver
+                            //   CMDS: A, B, C
+                            let board_id = parts[0].to_string();
+                            let (event, missing) = {
+                                let mut boards = mqtt_state.boards.write().await;
+                                let info = boards.entry(board_id.clone()).or_default();
+                                info.apply_payload(&payload);
+                                let missing = if info.queried { Vec::new() } else { info.missing_queries() };
+                                info.queried = true;
+                                (info.to_event("Unknown"), missing)
+                            };
+                            // Complete the handshake for boards we only know from their INFO reply.
+                            // Spawned so the publishes never wait on this (the event loop) task.
+                            if !missing.is_empty() {
+                                let hs_state = mqtt_state.clone();
+                                tokio::spawn(async move {
+                                    let topic = format!("miniverse/{}/info/command", board_id);
+                                    let mqtt = hs_state.mqtt.read().await;
+                                    for query in missing {
+                                        if let Err(e) = mqtt.publish(&topic, query.as_bytes()).await {
+                                            log::warn!("Handshake {} for {} failed: {}", query, board_id, e);
                                         }
                                     }
-                                } else if let Some(b) = seg.strip_prefix("BOARD:") {
-                                    board = b.trim().to_string();
-                                } else if let Some(f) = seg.strip_prefix("FIRMWARE:") {
-                                    firmware = f.trim().to_string();
-                                }
+                                });
                             }
-                            mqtt_state.broadcast(SystemEvent::SensorInfo { sensors, board, firmware });
+                            mqtt_state.broadcast(event);
                             continue;
                         }
                     }
 
+                    // Component state such as miniverse/<board>/temp/state -> typed reading
+                    if let Some(caps) = topic.strip_prefix("miniverse/") {
+                        let parts: Vec<&str> = caps.split('/').collect();
+                        if parts.len() >= 3 && parts[2] == "state" {
+                            if let Some(r) = readings::parse_reading(&payload) {
+                                mqtt_state.broadcast(SystemEvent::Reading {
+                                    board: parts[0].to_string(),
+                                    sensor: r.sensor,
+                                    value: r.value,
+                                    unit: r.unit,
+                                });
+                            }
+                        }
+                    }
+
                     mqtt_state.broadcast(SystemEvent::MqttMessage { topic, payload });
                 }
                 Ok(_) => {}
@@ -91,6 +124,15 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
+    log::info!("Starting rules engine...");
+    rules::spawn_rules(state.get_ref().clone());
+
+    log::info!("Starting alerts engine...");
+    alerts::spawn_alerts(state.get_ref().clone());
+
+    log::info!("Starting scheduler...");
+    scheduler::spawn_scheduler(state.get_ref().clone());
+
     let host = state.config.server.host.clone();
     let port = state.config.server.port;
 
@@ -107,7 +149,32 @@ async fn main() -> std::io::Result<()> {
             .wrap(cors)
             .app_data(state.clone())
             .route("/ws", web::get().to(websocket::ws_route))
-            .route("/api/ports", web::get().to(api_ports))
+            .route("/ws/v2", web::get().to(websocket::ws_v2_route))
+            .route("/api/login", web::post().to(auth::login_route))
+            // Public so clients can check compatibility before logging in
+            .route("/api/schema", web::get().to(schema::schema_route))
+            .service(
+                web::scope("/api")
+                    .wrap(from_fn(auth::require_auth))
+                    .route("/logout", web::post().to(auth::logout_route))
+                    .route("/ports", web::get().to(api_ports))
+                    .route("/scripts/run", web::post().to(scripts::run_route))
+                    .route("/metrics", web::get().to(websocket::metrics_route))
+                    .route("/audit", web::get().to(audit::audit_route))
+                    .route("/history", web::get().to(history::history_route))
+                    .route("/sessions", web::get().to(sessions::sessions_route))
+                    .route("/events", web::get().to(sse::events_route))
+                    // Terminal commands over REST for scripts and flows without a WebSocket
+                    .route("/serial/connect", web::post().to(serial::connect_route))
+                    .route("/serial", web::delete().to(serial::disconnect_route))
+                    .route("/serial/status", web::get().to(serial::status_route))
+                    .route("/devices/{id}/led", web::post().to(serial::led_route))
+                    .route("/devices/{id}/temp", web::get().to(serial::temp_route))
+                    .route("/mqtt/publish", web::post().to(serial::publish_route))
+                    .route("/mqtt/subscriptions", web::get().to(serial::subscriptions_route))
+                    .route("/mqtt/subscriptions", web::post().to(serial::subscribe_route))
+                    .route("/mqtt/subscriptions", web::delete().to(serial::unsubscribe_route)),
+            )
             .route("/health", web::get().to(|| async { "OK" }))
             .service(Files::new("/", "../frontend/dist").index_file("index.html"))
     })
@@ -1,3 +1,7 @@
 mod manager;
+mod subscriptions;
+mod topic;
 
 pub use manager::MqttManager;
+pub use subscriptions::{Changes, Subscriptions};
+pub use topic::{filter_covers, filters_overlap, topic_matches};
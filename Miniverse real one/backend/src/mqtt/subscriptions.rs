@@ -0,0 +1,185 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::mqtt::topic::{filter_covers, topic_matches};
+use crate::mqtt::MqttManager;
+
+/// Broker subscriptions to add and drop after a change
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub subscribe: Vec<String>,
+    pub unsubscribe: Vec<String>,
+}
+
+impl Changes {
+    pub async fn apply(self, mqtt: &MqttManager) {
+        for filter in &self.subscribe {
+            log::info!("MQTT: subscribing to {}", filter);
+            if let Err(e) = mqtt.subscribe(filter).await {
+                log::error!("MQTT subscribe {} failed: {}", filter, e);
+            }
+        }
+        for filter in &self.unsubscribe {
+            log::info!("MQTT: unsubscribing from {}", filter);
+            if let Err(e) = mqtt.unsubscribe(filter).await {
+                log::error!("MQTT unsubscribe {} failed: {}", filter, e);
+            }
+        }
+    }
+}
+
+/// Which MQTT filters are needed: the global set plus each WebSocket session's own.
+/// The broker holds one subscription per filter not already covered by a broader one,
+/// kept while at least one owner still needs it.
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    global: Vec<String>,
+    sessions: HashMap<u64, BTreeSet<String>>,
+    // What the broker currently has from us
+    active: BTreeSet<String>,
+}
+
+impl Subscriptions {
+    pub fn global(&self) -> &[String] {
+        &self.global
+    }
+
+    pub fn session(&self, session: u64) -> Vec<String> {
+        self.sessions.get(&session).map(|s| s.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Number of sessions holding exactly this filter
+    pub fn refs(&self, filter: &str) -> usize {
+        self.sessions.values().filter(|s| s.contains(filter)).count()
+    }
+
+    /// Every session-owned filter with its reference count
+    pub fn session_filters(&self) -> Vec<(String, usize)> {
+        let all: BTreeSet<&String> = self.sessions.values().flatten().collect();
+        all.into_iter().map(|f| (f.clone(), self.refs(f))).collect()
+    }
+
+    /// Is `topic` covered by a global filter
+    pub fn wants_global(&self, topic: &str) -> bool {
+        self.global.iter().any(|f| topic_matches(f, topic))
+    }
+
+    /// Should this session see a message on `topic`
+    pub fn wants(&self, session: u64, topic: &str) -> bool {
+        self.wants_global(topic)
+            || self.sessions.get(&session).is_some_and(|s| s.iter().any(|f| topic_matches(f, topic)))
+    }
+
+    pub fn add_global(&mut self, filter: &str) -> Changes {
+        if !self.global.iter().any(|f| f == filter) {
+            self.global.push(filter.to_string());
+        }
+        self.reconcile()
+    }
+
+    pub fn remove_global(&mut self, filter: &str) -> Result<Changes, String> {
+        let pos = self.global.iter().position(|f| f == filter).ok_or(format!("{} is not a global subscription", filter))?;
+        self.global.remove(pos);
+        Ok(self.reconcile())
+    }
+
+    pub fn add(&mut self, session: u64, filter: &str) -> Result<Changes, String> {
+        if !self.sessions.entry(session).or_default().insert(filter.to_string()) {
+            return Err(format!("Already subscribed to {}", filter));
+        }
+        Ok(self.reconcile())
+    }
+
+    pub fn remove(&mut self, session: u64, filter: &str) -> Result<Changes, String> {
+        let removed = self.sessions.get_mut(&session).is_some_and(|s| s.remove(filter));
+        if !removed {
+            return Err(format!("You are not subscribed to {} (see 'mqtt subs')", filter));
+        }
+        Ok(self.reconcile())
+    }
+
+    /// Release everything a closed session held
+    pub fn drop_session(&mut self, session: u64) -> Changes {
+        self.sessions.remove(&session);
+        self.reconcile()
+    }
+
+    fn reconcile(&mut self) -> Changes {
+        self.sessions.retain(|_, s| !s.is_empty());
+        let wanted: BTreeSet<&String> = self.global.iter().chain(self.sessions.values().flatten()).collect();
+        let needed: BTreeSet<String> = wanted
+            .iter()
+            .filter(|f| !wanted.iter().any(|g| g != *f && filter_covers(g, f)))
+            .map(|f| f.to_string())
+            .collect();
+        let changes = Changes {
+            subscribe: needed.difference(&self.active).cloned().collect(),
+            unsubscribe: self.active.difference(&needed).cloned().collect(),
+        };
+        self.active = needed;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subs(changes: &Changes) -> (Vec<&str>, Vec<&str>) {
+        (
+            changes.subscribe.iter().map(String::as_str).collect(),
+            changes.unsubscribe.iter().map(String::as_str).collect(),
+        )
+    }
+
+    #[test]
+    fn broad_filter_absorbs_narrower_ones() {
+        let mut s = Subscriptions::default();
+        s.add(1, "miniverse/b1/temp").unwrap();
+        s.add(2, "miniverse/+/led").unwrap();
+        let c = s.add(3, "miniverse/#").unwrap();
+        assert_eq!(subs(&c), (vec!["miniverse/#"], vec!["miniverse/+/led", "miniverse/b1/temp"]));
+        // Already covered: nothing new at the broker
+        let c = s.add(1, "miniverse/b2/temp").unwrap();
+        assert_eq!(subs(&c), (vec![], vec![]));
+    }
+
+    #[test]
+    fn dropping_the_broad_filter_restores_the_narrow_ones() {
+        let mut s = Subscriptions::default();
+        s.add(1, "miniverse/b1/temp").unwrap();
+        s.add(2, "miniverse/#").unwrap();
+        let c = s.remove(2, "miniverse/#").unwrap();
+        assert_eq!(subs(&c), (vec!["miniverse/b1/temp"], vec!["miniverse/#"]));
+        assert!(s.remove(2, "miniverse/#").is_err());
+    }
+
+    #[test]
+    fn drop_session_keeps_what_others_still_need() {
+        let mut s = Subscriptions::default();
+        s.add_global("miniverse/+/info/state");
+        s.add(1, "lab/door").unwrap();
+        s.add(2, "lab/door").unwrap();
+        s.add(1, "lab/window").unwrap();
+        s.add(1, "miniverse/b1/info/state").unwrap();
+        assert_eq!(s.refs("lab/door"), 2);
+
+        let c = s.drop_session(1);
+        assert_eq!(subs(&c), (vec![], vec!["lab/window"]));
+        assert_eq!(s.refs("lab/door"), 1);
+        assert!(s.session(1).is_empty());
+
+        let c = s.drop_session(2);
+        assert_eq!(subs(&c), (vec![], vec!["lab/door"]));
+    }
+
+    #[test]
+    fn sessions_see_global_topics_and_their_own() {
+        let mut s = Subscriptions::default();
+        s.add_global("miniverse/+/info/state");
+        s.add(1, "lab/#").unwrap();
+        assert!(s.wants(1, "lab/door"));
+        assert!(s.wants(2, "miniverse/b1/info/state"));
+        assert!(!s.wants(2, "lab/door"));
+        assert!(!s.wants_global("lab/door"));
+    }
+}
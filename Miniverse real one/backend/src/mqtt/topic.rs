@@ -0,0 +1,80 @@
+/// MQTT filter matching: `+` matches one level, a trailing `#` matches the rest
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut f = filter.split('/');
+    let mut t = topic.split('/');
+    loop {
+        match (f.next(), t.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(a), Some(b)) if a == b => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// True when every topic matched by `inner` is also matched by `outer`
+pub fn filter_covers(outer: &str, inner: &str) -> bool {
+    let mut o = outer.split('/');
+    let mut i = inner.split('/');
+    loop {
+        match (o.next(), i.next()) {
+            (Some("#"), _) => return true,
+            (_, Some("#")) => return false,
+            (Some("+"), Some(_)) => {}
+            (Some(a), Some(b)) if a == b => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// True when some topic is matched by both filters
+pub fn filters_overlap(a: &str, b: &str) -> bool {
+    let mut x = a.split('/');
+    let mut y = b.split('/');
+    loop {
+        match (x.next(), y.next()) {
+            (Some("#"), _) | (_, Some("#")) => return true,
+            (Some("+"), Some(_)) | (Some(_), Some("+")) => {}
+            (Some(p), Some(q)) if p == q => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(topic_matches("miniverse/+/temp/state", "miniverse/b1/temp/state"));
+        assert!(topic_matches("miniverse/#", "miniverse/b1/temp/state"));
+        assert!(topic_matches("miniverse/#", "miniverse"));
+        assert!(!topic_matches("miniverse/+", "miniverse/b1/temp"));
+        assert!(!topic_matches("miniverse/+", "miniverse"));
+    }
+
+    #[test]
+    fn covers_only_what_the_outer_filter_matches() {
+        assert!(filter_covers("miniverse/#", "miniverse/+/led/command"));
+        assert!(filter_covers("miniverse/+/led/#", "miniverse/b1/led/command"));
+        assert!(filter_covers("a/#", "a/#"));
+        // A narrower filter never covers a wildcard level or tail
+        assert!(!filter_covers("miniverse/b1/#", "miniverse/+/led"));
+        assert!(!filter_covers("miniverse/+/led", "miniverse/b1/#"));
+        assert!(!filter_covers("miniverse/+", "miniverse/b1/led"));
+    }
+
+    #[test]
+    fn overlap_needs_one_shared_topic() {
+        assert!(filters_overlap("miniverse/secret/#", "miniverse/#"));
+        assert!(filters_overlap("miniverse/+/temp", "miniverse/b1/+"));
+        assert!(filters_overlap("a/+/c", "a/b/#"));
+        assert!(!filters_overlap("miniverse/secret/#", "miniverse/public/+"));
+        assert!(!filters_overlap("a/+", "a"));
+        assert!(!filters_overlap("a/b", "a/b/c"));
+    }
+}
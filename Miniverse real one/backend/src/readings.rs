@@ -0,0 +1,37 @@
+/// A numeric sensor value recognised in firmware output such as `TEMP:25.3C` or `DIST:12.4cm`
+#[derive(Debug, Clone)]
+pub struct Reading {
+    pub sensor: String,
+    pub value: f64,
+    pub unit: String,
+}
+
+/// Canonical sensor name, so `TEMP`, `temperature` and `temp` compare equal
+pub fn normalize_sensor(name: &str) -> String {
+    match name.trim().to_lowercase().as_str() {
+        "temp" | "temperature" => "temp".to_string(),
+        "dist" | "distance" => "distance".to_string(),
+        "hum" | "humidity" => "humidity".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Parse `NAME:<number><unit>`; error lines (`DIST:ERR`, `ERROR: ...`) yield None
+pub fn parse_reading(text: &str) -> Option<Reading> {
+    let (name, rest) = text.trim().split_once(':')?;
+    if name.trim().is_empty() || name.eq_ignore_ascii_case("error") {
+        return None;
+    }
+    let (value, unit) = split_number(rest.trim())?;
+    Some(Reading { sensor: normalize_sensor(name), value, unit: unit.trim().to_string() })
+}
+
+/// Split `12.4cm` into the leading number and the unit that follows it
+pub fn split_number(s: &str) -> Option<(f64, &str)> {
+    let end = s
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+    s[..end].parse::<f64>().ok().map(|v| (v, &s[end..]))
+}
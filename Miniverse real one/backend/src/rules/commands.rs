@@ -0,0 +1,57 @@
+use crate::events::SystemEvent;
+use crate::rules::rule::parse_rule;
+use crate::shell::{Invocation, Spec};
+use crate::state::AppState;
+
+const USAGE: &str = "Usage:\n  rule add if distance < 10 [hyst 2] [debounce 1s] then light on; lcd show \"TOO CLOSE\"\n  rule add if mqtt miniverse/+/led/state == ON then publish miniverse/alerts led-on\n  rule list\n  rule rm <id>\n";
+
+const ADD: Spec = Spec::new("rule add if <cond> then <cmd>[; <cmd>]", 1, None).options_first();
+const RM: Spec = Spec::new("rule rm <id>", 1, Some(1));
+
+/// `rule add/list/rm`
+pub async fn handle_rule(inv: &Invocation, state: &AppState) -> SystemEvent {
+    let sub = inv.subcommand();
+    match sub.as_ref().map_or("", |s| s.name()) {
+        "" | "list" | "ls" => {
+            let rules = state.rules.read().await;
+            if rules.rules().is_empty() {
+                return SystemEvent::Output { content: "No rules".to_string() };
+            }
+            let mut out = format!("Rules ({}):\n", rules.rules().len());
+            for rule in rules.rules() {
+                out.push_str(&format!("#{} {}\n", rule.id, rule.describe()));
+            }
+            SystemEvent::Output { content: out }
+        }
+        "add" => {
+            let text = match sub.as_ref().map(|s| s.args(&ADD)) {
+                Some(Ok(args)) => args.rest(0),
+                Some(Err(message)) => return SystemEvent::Error { source: "cli".to_string(), message },
+                None => return SystemEvent::Output { content: USAGE.to_string() },
+            };
+            let rule = match parse_rule(&text) {
+                Ok(r) => r,
+                Err(e) => return SystemEvent::Error { source: "rule".to_string(), message: e },
+            };
+            let mut rules = state.rules.write().await;
+            match rules.add(rule) {
+                Ok(rule) => SystemEvent::Output { content: format!("Rule #{} added: {}", rule.id, rule.describe()) },
+                Err(e) => SystemEvent::Error { source: "rule".to_string(), message: e },
+            }
+        }
+        "rm" | "remove" | "del" => {
+            let id = match sub.as_ref().map(|s| s.args(&RM).and_then(|a| a.require::<u32>(0, "rule id"))) {
+                Some(Ok(id)) => id,
+                Some(Err(message)) => return SystemEvent::Error { source: "cli".to_string(), message },
+                None => return SystemEvent::Output { content: USAGE.to_string() },
+            };
+            let mut rules = state.rules.write().await;
+            match rules.remove(id) {
+                Ok(Some(rule)) => SystemEvent::Output { content: format!("Rule #{} removed ({})", rule.id, rule.describe()) },
+                Ok(None) => SystemEvent::Error { source: "rule".to_string(), message: format!("No rule #{}", id) },
+                Err(e) => SystemEvent::Error { source: "rule".to_string(), message: e },
+            }
+        }
+        _ => SystemEvent::Output { content: USAGE.to_string() },
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::SystemEvent;
+use crate::mqtt::topic_matches;
+use crate::readings::normalize_sensor;
+use crate::rules::rule::{Condition, Rule};
+use crate::state::AppState;
+
+/// Per-rule edge tracking; lives only in the engine task
+struct RuleState {
+    // Cleared when the rule fires, set again once the reading is released past the hysteresis band
+    armed: bool,
+    pending_since: Option<Instant>,
+    last_fired: Option<Instant>,
+}
+
+impl Default for RuleState {
+    fn default() -> Self {
+        Self { armed: true, pending_since: None, last_fired: None }
+    }
+}
+
+// Floor on the gap between MQTT firings. `Rule::validate` refuses direct self-triggers; this
+// bounds loops through several rules (one publishes what the next watches, and back).
+const MIN_MQTT_GAP_MS: u64 = 250;
+
+/// Watch the event bus and run rule actions when their conditions trigger
+pub fn spawn_rules(state: AppState) {
+    tokio::spawn(async move {
+        log::info!("Rules engine started");
+        let mut rx = state.subscribe();
+        let mut states: HashMap<u32, RuleState> = HashMap::new();
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(n)) => {
+                    log::warn!("Rules engine skipped {} events", n);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            let rules: Vec<Rule> = state.rules.read().await.rules().to_vec();
+            states.retain(|id, _| rules.iter().any(|r| r.id == *id));
+            let now = Instant::now();
+
+            for rule in rules {
+                let rs = states.entry(rule.id).or_default();
+                let trigger = match (&rule.condition, &event) {
+                    (Condition::Reading { sensor, op, threshold }, SystemEvent::Reading { board, sensor: got, value, unit }) => {
+                        if normalize_sensor(got) != *sensor {
+                            continue;
+                        }
+                        if !op.holds(*value, *threshold) {
+                            rs.pending_since = None;
+                            if rule.released(*value) {
+                                rs.armed = true;
+                            }
+                            continue;
+                        }
+                        if !rs.armed {
+                            continue;
+                        }
+                        let since = *rs.pending_since.get_or_insert(now);
+                        if now.duration_since(since) < Duration::from_millis(rule.debounce_ms) {
+                            continue;
+                        }
+                        rs.armed = false;
+                        rs.pending_since = None;
+                        format!("{} {}{} on {}", got, value, unit, board)
+                    }
+                    (Condition::Mqtt { topic, payload }, SystemEvent::MqttMessage { topic: got, payload: body }) => {
+                        if !topic_matches(topic, got) {
+                            continue;
+                        }
+                        if payload.as_ref().is_some_and(|p| !p.eq_ignore_ascii_case(body.trim())) {
+                            continue;
+                        }
+                        let gap = Duration::from_millis(rule.debounce_ms.max(MIN_MQTT_GAP_MS));
+                        if rs.last_fired.is_some_and(|t| now.duration_since(t) < gap) {
+                            continue;
+                        }
+                        format!("{} {}", got, body)
+                    }
+                    _ => continue,
+                };
+                rs.last_fired = Some(now);
+
+                let state = state.clone();
+                tokio::spawn(async move { fire(&rule, trigger, &state).await });
+            }
+        }
+    });
+}
+
+async fn fire(rule: &Rule, trigger: String, state: &AppState) {
+    log::info!("Rule #{} triggered by {}", rule.id, trigger);
+    let mut ok = true;
+    let mut outputs = Vec::new();
+    for action in &rule.actions {
+        let (action_ok, output) = action.run(state).await;
+        ok &= action_ok;
+        outputs.push(format!("{}: {}", action.describe(), output));
+    }
+    state.broadcast(SystemEvent::RuleFired {
+        id: rule.id,
+        rule: rule.describe(),
+        trigger,
+        ok,
+        output: outputs.join("\n"),
+    });
+}
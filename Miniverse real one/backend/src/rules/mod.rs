@@ -0,0 +1,7 @@
+mod commands;
+mod engine;
+mod rule;
+
+pub use commands::handle_rule;
+pub use engine::spawn_rules;
+pub use rule::{Rule, RuleSet};
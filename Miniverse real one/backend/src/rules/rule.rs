@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::actions::Action;
+use crate::config::RulesConfig;
+use crate::mqtt::filters_overlap;
+use crate::readings::{normalize_sensor, split_number};
+use crate::shell::{split_unquoted, tokenize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Op {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+}
+
+impl Op {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "<" => Some(Op::Lt),
+            "<=" => Some(Op::Le),
+            ">" => Some(Op::Gt),
+            ">=" => Some(Op::Ge),
+            "==" | "=" => Some(Op::Eq),
+            "!=" => Some(Op::Ne),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+        }
+    }
+
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Op::Lt => value < threshold,
+            Op::Le => value <= threshold,
+            Op::Gt => value > threshold,
+            Op::Ge => value >= threshold,
+            Op::Eq => (value - threshold).abs() < f64::EPSILON,
+            Op::Ne => (value - threshold).abs() >= f64::EPSILON,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    // Typed reading, e.g. distance < 10
+    Reading { sensor: String, op: Op, threshold: f64 },
+    // Any message on a topic filter, optionally with an exact (case-insensitive) payload
+    Mqtt {
+        topic: String,
+        #[serde(default)]
+        payload: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: u32,
+    pub condition: Condition,
+    // Readings: how far back past the threshold a value must go before the rule can fire again
+    #[serde(default)]
+    pub hysteresis: f64,
+    // Readings: how long the condition must hold before firing. MQTT: minimum gap between firings.
+    #[serde(default)]
+    pub debounce_ms: u64,
+    pub actions: Vec<Action>,
+}
+
+impl Rule {
+    /// Refuse rules whose own action would trigger them again, which would loop without end
+    pub fn validate(&self) -> Result<(), String> {
+        let Condition::Mqtt { topic: trigger, .. } = &self.condition else { return Ok(()) };
+        match self.actions.iter().find_map(|a| a.publish_topic().filter(|t| filters_overlap(trigger, t))) {
+            Some(topic) => Err(format!("Rule would trigger itself: it publishes to '{}', which matches '{}'", topic, trigger)),
+            None => Ok(()),
+        }
+    }
+
+    /// True once a reading has moved back past the threshold by the hysteresis margin
+    pub fn released(&self, value: f64) -> bool {
+        match &self.condition {
+            Condition::Reading { op, threshold, .. } => match op {
+                Op::Lt | Op::Le => value > threshold + self.hysteresis,
+                Op::Gt | Op::Ge => value < threshold - self.hysteresis,
+                Op::Eq | Op::Ne => !op.holds(value, *threshold),
+            },
+            Condition::Mqtt { .. } => true,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut s = match &self.condition {
+            Condition::Reading { sensor, op, threshold } => format!("if {} {} {}", sensor, op.symbol(), threshold),
+            Condition::Mqtt { topic, payload: Some(p) } => format!("if mqtt {} == {}", topic, p),
+            Condition::Mqtt { topic, payload: None } => format!("if mqtt {}", topic),
+        };
+        if self.hysteresis > 0.0 {
+            s.push_str(&format!(" hyst {}", self.hysteresis));
+        }
+        if self.debounce_ms > 0 {
+            s.push_str(&format!(" debounce {}ms", self.debounce_ms));
+        }
+        let actions: Vec<String> = self.actions.iter().map(|a| a.describe()).collect();
+        format!("{} then {}", s, actions.join("; "))
+    }
+}
+
+/// Parse `if <cond> [hyst <n>] [debounce <dur>] then <action>[; <action>...]` (without the id)
+pub fn parse_rule(text: &str) -> Result<Rule, String> {
+    const USAGE: &str = "Usage: rule add if <sensor> <op> <value> [hyst <n>] [debounce <dur>] then <cmd>[; <cmd>]\n       rule add if mqtt <topic> [== <payload>] [debounce <dur>] then <cmd>[; <cmd>]";
+    let text = text.trim();
+    let body = text.strip_prefix("if ").ok_or(USAGE)?;
+    let (cond, actions) = body.split_once(" then ").ok_or(USAGE)?;
+
+    let toks: Vec<String> = tokenize(cond)?.into_iter().map(|t| t.text).collect();
+    let toks: Vec<&str> = toks.iter().map(String::as_str).collect();
+    let (condition, mut i) = if toks.first().is_some_and(|t| t.eq_ignore_ascii_case("mqtt")) {
+        let topic = toks.get(1).ok_or(USAGE)?.to_string();
+        match toks.get(2).copied() {
+            Some("==") | Some("=") => {
+                let payload = toks.get(3).ok_or(USAGE)?.to_string();
+                (Condition::Mqtt { topic, payload: Some(payload) }, 4)
+            }
+            _ => (Condition::Mqtt { topic, payload: None }, 2),
+        }
+    } else {
+        let sensor = normalize_sensor(toks.first().ok_or(USAGE)?);
+        let op = toks.get(1).and_then(|o| Op::parse(o)).ok_or_else(|| format!("Unknown operator in '{}'. Use < <= > >= == !=", cond))?;
+        let threshold = toks.get(2).and_then(|v| parse_number(v)).ok_or_else(|| format!("Threshold must be a number in '{}'", cond))?;
+        (Condition::Reading { sensor, op, threshold }, 3)
+    };
+
+    let mut hysteresis = 0.0;
+    let mut debounce_ms = 0;
+    while i < toks.len() {
+        match (toks[i], toks.get(i + 1)) {
+            ("hyst" | "hysteresis", Some(v)) => {
+                hysteresis = parse_number(v).ok_or_else(|| format!("Bad hysteresis '{}'", v))?;
+            }
+            ("debounce", Some(v)) => {
+                debounce_ms = crate::scripts::parse_duration_ms(v).ok_or_else(|| format!("Bad debounce '{}'", v))?;
+            }
+            (other, _) => return Err(format!("Unexpected '{}' in condition. {}", other, USAGE)),
+        }
+        i += 2;
+    }
+
+    let actions = split_unquoted(actions, ';')
+        .iter()
+        .map(|a| Action::parse(a))
+        .collect::<Result<Vec<_>, _>>()?;
+    if actions.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let rule = Rule { id: 0, condition, hysteresis, debounce_ms, actions };
+    rule.validate()?;
+    Ok(rule)
+}
+
+/// Leading number of `10cm` / `30.5C` / `-4`
+fn parse_number(s: &str) -> Option<f64> {
+    split_number(s).map(|(v, _)| v)
+}
+
+/// Rule table persisted as JSON at `RulesConfig::store_path`
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    next_id: u32,
+    path: PathBuf,
+}
+
+impl RuleSet {
+    /// Load saved rules, or seed from config on first start
+    pub fn load(cfg: &RulesConfig) -> Self {
+        let path = PathBuf::from(&cfg.store_path);
+        let rules: Vec<Rule> = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::error!("Rules: cannot parse {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => cfg.rules.clone(),
+        };
+        let rules: Vec<Rule> = rules
+            .into_iter()
+            .filter(|r| match r.validate() {
+                Ok(()) => true,
+                Err(e) => { log::error!("Rules: dropping rule #{}: {}", r.id, e); false }
+            })
+            .collect();
+        let next_id = rules.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+        log::info!("Rules: {} rule(s) loaded", rules.len());
+        Self { rules, next_id, path }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    // Changes are saved before they take effect, so a failed write changes nothing
+
+    pub fn add(&mut self, mut rule: Rule) -> Result<Rule, String> {
+        rule.id = self.next_id;
+        let mut rules = self.rules.clone();
+        rules.push(rule.clone());
+        self.save(&rules)?;
+        self.rules = rules;
+        self.next_id += 1;
+        Ok(rule)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<Option<Rule>, String> {
+        let Some(pos) = self.rules.iter().position(|r| r.id == id) else { return Ok(None) };
+        let mut rules = self.rules.clone();
+        let rule = rules.remove(pos);
+        self.save(&rules)?;
+        self.rules = rules;
+        Ok(Some(rule))
+    }
+
+    fn save(&self, rules: &[Rule]) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("Create {} failed: {}", dir.display(), e))?;
+            }
+        }
+        let text = serde_json::to_string_pretty(rules).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, text).map_err(|e| format!("Write {} failed: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_reading_rule() {
+        let rule = parse_rule("if distance < 10cm hyst 2 debounce 500ms then light on; publish alarm/door open").unwrap();
+        assert!(matches!(rule.condition, Condition::Reading { ref sensor, op: Op::Lt, threshold } if sensor == "distance" && threshold == 10.0));
+        assert_eq!(rule.hysteresis, 2.0);
+        assert_eq!(rule.debounce_ms, 500);
+        assert_eq!(rule.actions.len(), 2);
+        assert!(rule.released(12.5));
+        assert!(!rule.released(11.0));
+    }
+
+    #[test]
+    fn parses_an_mqtt_rule_with_payload() {
+        let rule = parse_rule("if mqtt miniverse/+/button/state == PRESSED then light off").unwrap();
+        assert!(matches!(rule.condition, Condition::Mqtt { ref topic, payload: Some(ref p) } if topic == "miniverse/+/button/state" && p == "PRESSED"));
+    }
+
+    #[test]
+    fn rejects_a_rule_that_publishes_to_its_own_trigger() {
+        assert!(parse_rule("if mqtt home/# then publish home/lamp on").is_err());
+        assert!(parse_rule("if mqtt home/+/state then mqtt pub home/lamp/state on").is_err());
+        assert!(parse_rule("if mqtt home/button then publish home/lamp on").is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!(parse_rule("if temp ~ 3 then light on").is_err());
+        assert!(parse_rule("if temp > hot then light on").is_err());
+        assert!(parse_rule("if temp > 30").is_err());
+    }
+}
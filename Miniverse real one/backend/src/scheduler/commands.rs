@@ -0,0 +1,124 @@
+use chrono::Local;
+use std::time::Duration;
+
+use crate::actions::Action;
+use crate::events::SystemEvent;
+use crate::scheduler::job::{parse_cron, Job};
+use crate::shell::{Invocation, Spec};
+use crate::state::AppState;
+
+const USAGE: &str = "Usage:\n  schedule add \"<cron>\" <command>\n  schedule add \"<cron>\" publish <topic> <payload>\n  schedule add @hourly <command>\n  schedule list\n  schedule rm <id>\n";
+
+/// Background loop: once a second, fire every job with an occurrence since the last check
+pub fn spawn_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        log::info!("Scheduler started");
+        let mut last = Local::now();
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            ticker.tick().await;
+            let now = Local::now();
+            let due: Vec<Job> = {
+                let scheduler = state.scheduler.read().await;
+                scheduler
+                    .jobs()
+                    .iter()
+                    .filter(|j| {
+                        j.schedule()
+                            .ok()
+                            .and_then(|s| s.after(&last).next())
+                            .is_some_and(|t| t <= now)
+                    })
+                    .cloned()
+                    .collect()
+            };
+            last = now;
+            for job in due {
+                let state = state.clone();
+                tokio::spawn(async move { run_job(&job, &state).await });
+            }
+        }
+    });
+}
+
+async fn run_job(job: &Job, state: &AppState) {
+    let (ok, output) = job.action.run(state).await;
+    if ok {
+        log::info!("Job #{} ({}): {}", job.id, job.describe(), output);
+    } else {
+        log::warn!("Job #{} ({}) failed: {}", job.id, job.describe(), output);
+    }
+    state.broadcast(SystemEvent::JobRun { id: job.id, action: job.describe(), ok, output });
+}
+
+const ADD: Spec = Spec::new("schedule add \"<cron>\" <command>", 2, None).options_first();
+const RM: Spec = Spec::new("schedule rm <id>", 1, Some(1));
+
+/// `schedule add/list/rm`
+pub async fn handle_schedule(inv: &Invocation, state: &AppState) -> SystemEvent {
+    let sub = inv.subcommand();
+    match sub.as_ref().map_or("", |s| s.name()) {
+        "" | "list" | "ls" => {
+            let scheduler = state.scheduler.read().await;
+            if scheduler.jobs().is_empty() {
+                return SystemEvent::Output { content: "No scheduled jobs".to_string() };
+            }
+            let mut out = format!("Scheduled jobs ({}):\n", scheduler.jobs().len());
+            for job in scheduler.jobs() {
+                let next = job
+                    .next_run()
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                out.push_str(&format!("#{} [{}] {} (next: {})\n", job.id, job.cron, job.describe(), next));
+            }
+            SystemEvent::Output { content: out }
+        }
+        "add" => {
+            let Some(sub) = sub else { return SystemEvent::Output { content: USAGE.to_string() } };
+            let (cron, action) = match parse_add(&sub) {
+                Ok(v) => v,
+                Err(e) => return SystemEvent::Error { source: "schedule".to_string(), message: e },
+            };
+            let mut scheduler = state.scheduler.write().await;
+            match scheduler.add(&cron, action) {
+                Ok(job) => SystemEvent::Output {
+                    content: format!(
+                        "Job #{} scheduled [{}] {} (next: {})",
+                        job.id,
+                        job.cron,
+                        job.describe(),
+                        job.next_run().map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "never".to_string())
+                    ),
+                },
+                Err(e) => SystemEvent::Error { source: "schedule".to_string(), message: e },
+            }
+        }
+        "rm" | "remove" | "del" => {
+            let id = match sub.as_ref().map(|s| s.args(&RM).and_then(|a| a.require::<u32>(0, "job id"))) {
+                Some(Ok(id)) => id,
+                Some(Err(message)) => return SystemEvent::Error { source: "cli".to_string(), message },
+                None => return SystemEvent::Output { content: USAGE.to_string() },
+            };
+            let mut scheduler = state.scheduler.write().await;
+            match scheduler.remove(id) {
+                Ok(Some(job)) => SystemEvent::Output { content: format!("Job #{} removed ({})", job.id, job.describe()) },
+                Ok(None) => SystemEvent::Error { source: "schedule".to_string(), message: format!("No job #{}", id) },
+                Err(e) => SystemEvent::Error { source: "schedule".to_string(), message: e },
+            }
+        }
+        _ => SystemEvent::Output { content: USAGE.to_string() },
+    }
+}
+
+/// Split `"<cron>" <action...>` / `@shorthand <action...>`
+fn parse_add(inv: &Invocation) -> Result<(String, Action), String> {
+    let args = inv.args(&ADD)?;
+    let cron = args.get(0).unwrap_or("");
+    // An unquoted cron would have been cut at its first space
+    if !cron.starts_with('@') && !cron.contains(char::is_whitespace) {
+        return Err(args.error_at(0, "Quote the cron expression, e.g. \"*/5 * * * *\", or use @hourly/@daily"));
+    }
+    parse_cron(cron).map_err(|e| args.error_at(0, &e))?;
+    let action = Action::parse(&args.rest(1))?;
+    Ok((cron.to_string(), action))
+}
@@ -0,0 +1,201 @@
+use chrono::{DateTime, Local};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::actions::Action;
+use crate::config::SchedulerConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u32,
+    pub cron: String,
+    pub action: Action,
+}
+
+impl Job {
+    pub fn schedule(&self) -> Result<Schedule, String> {
+        parse_cron(&self.cron)
+    }
+
+    pub fn next_run(&self) -> Option<DateTime<Local>> {
+        self.schedule().ok().and_then(|s| s.upcoming(Local).next())
+    }
+
+    pub fn describe(&self) -> String {
+        self.action.describe()
+    }
+}
+
+/// Accept standard 5-field cron (`min hour dom mon dow`, Sunday = 0 or 7), the crate's
+/// 6/7-field form with seconds (where Sunday = 1), and shorthands such as `@hourly`.
+pub fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let expr = expr.trim();
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let full = if !expr.starts_with('@') && fields.len() == 5 {
+        let dow = standard_dow(fields[4]).map_err(|e| format!("Invalid cron expression '{}': {}", expr, e))?;
+        format!("0 {} {}", fields[..4].join(" "), dow)
+    } else {
+        expr.to_string()
+    };
+    Schedule::from_str(&full).map_err(|e| format!("Invalid cron expression '{}': {}", expr, e))
+}
+
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Rewrite a standard day-of-week field as day names, which the crate numbers differently
+fn standard_dow(field: &str) -> Result<String, String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+    let day = |s: &str| -> Result<usize, String> {
+        if let Some(i) = DAY_NAMES.iter().position(|d| d.eq_ignore_ascii_case(s)) {
+            return Ok(i);
+        }
+        match s.parse::<usize>() {
+            Ok(n @ 0..=7) => Ok(n),
+            _ => Err(format!("bad day of week '{}'", s)),
+        }
+    };
+    let mut days = [false; 7];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<usize>().ok().filter(|&s| s > 0).ok_or_else(|| format!("bad step in '{}'", part))?),
+            None => (part, 1),
+        };
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (0, 6),
+            Some((a, b)) => (day(a)?, day(b)?),
+            // `n/step` runs to the end of the week
+            None if step > 1 => (day(range)?, 6),
+            None => (day(range)?, day(range)?),
+        };
+        if from > to {
+            return Err(format!("bad range '{}'", range));
+        }
+        // 7 is Sunday again
+        for d in (from..=to).step_by(step) {
+            days[d % 7] = true;
+        }
+    }
+    Ok(DAY_NAMES.iter().zip(days).filter(|(_, on)| *on).map(|(name, _)| *name).collect::<Vec<_>>().join(","))
+}
+
+/// Job table persisted as JSON at `SchedulerConfig::store_path`
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    next_id: u32,
+    path: PathBuf,
+}
+
+impl Scheduler {
+    /// Load saved jobs, or seed from config on first start
+    pub fn load(cfg: &SchedulerConfig) -> Self {
+        let path = PathBuf::from(&cfg.store_path);
+        let jobs: Vec<Job> = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::error!("Scheduler: cannot parse {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => cfg.jobs.clone(),
+        };
+        let jobs: Vec<Job> = jobs
+            .into_iter()
+            .filter(|j| match j.schedule() {
+                Ok(_) => true,
+                Err(e) => { log::error!("Scheduler: dropping job #{}: {}", j.id, e); false }
+            })
+            .collect();
+        let next_id = jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        log::info!("Scheduler: {} job(s) loaded", jobs.len());
+        Self { jobs, next_id, path }
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    // Changes are saved before they take effect, so a failed write changes nothing
+
+    pub fn add(&mut self, cron: &str, action: Action) -> Result<Job, String> {
+        parse_cron(cron)?;
+        let job = Job { id: self.next_id, cron: cron.trim().to_string(), action };
+        let mut jobs = self.jobs.clone();
+        jobs.push(job.clone());
+        self.save(&jobs)?;
+        self.jobs = jobs;
+        self.next_id += 1;
+        Ok(job)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<Option<Job>, String> {
+        let Some(pos) = self.jobs.iter().position(|j| j.id == id) else { return Ok(None) };
+        let mut jobs = self.jobs.clone();
+        let job = jobs.remove(pos);
+        self.save(&jobs)?;
+        self.jobs = jobs;
+        Ok(Some(job))
+    }
+
+    fn save(&self, jobs: &[Job]) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("Create {} failed: {}", dir.display(), e))?;
+            }
+        }
+        let text = serde_json::to_string_pretty(jobs).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, text).map_err(|e| format!("Write {} failed: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Weekday};
+
+    fn weekdays(expr: &str) -> Vec<Weekday> {
+        let mut days: Vec<Weekday> = parse_cron(expr).unwrap().upcoming(Local).take(50).map(|t| t.weekday()).collect();
+        days.sort_by_key(|d| d.num_days_from_sunday());
+        days.dedup();
+        days
+    }
+
+    #[test]
+    fn standard_day_numbers_start_at_sunday() {
+        assert_eq!(standard_dow("0").unwrap(), "SUN");
+        assert_eq!(standard_dow("7").unwrap(), "SUN");
+        assert_eq!(standard_dow("1-5").unwrap(), "MON,TUE,WED,THU,FRI");
+        assert_eq!(standard_dow("5-7").unwrap(), "SUN,FRI,SAT");
+        assert_eq!(standard_dow("*/2").unwrap(), "SUN,TUE,THU,SAT");
+        assert_eq!(standard_dow("sat,sun").unwrap(), "SUN,SAT");
+        assert_eq!(standard_dow("*").unwrap(), "*");
+        assert!(standard_dow("8").is_err());
+        assert!(standard_dow("5-1").is_err());
+    }
+
+    #[test]
+    fn weekday_jobs_fire_monday_to_friday() {
+        use Weekday::*;
+        assert_eq!(weekdays("0 7 * * 1-5"), [Mon, Tue, Wed, Thu, Fri]);
+    }
+
+    #[test]
+    fn sunday_zero_is_accepted() {
+        assert_eq!(weekdays("* * * * 0"), [Weekday::Sun]);
+        assert_eq!(weekdays("0 * * * 7"), [Weekday::Sun]);
+    }
+
+    #[test]
+    fn six_field_form_keeps_the_crate_numbering() {
+        // Seconds first; the crate counts Sunday as 1
+        assert_eq!(weekdays("0 0 12 * * 1"), [Weekday::Sun]);
+    }
+
+    #[test]
+    fn shorthands_and_bad_expressions() {
+        assert!(parse_cron("@hourly").is_ok());
+        assert!(parse_cron("61 * * * *").is_err());
+    }
+}
@@ -0,0 +1,5 @@
+mod commands;
+mod job;
+
+pub use commands::{handle_schedule, spawn_scheduler};
+pub use job::{Job, Scheduler};
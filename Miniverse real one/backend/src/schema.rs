@@ -0,0 +1,18 @@
+use actix_web::HttpResponse;
+use schemars::schema_for;
+use serde_json::json;
+
+use crate::events::{ClientRequest, SystemEvent, PROTOCOL_VERSION};
+use crate::websocket::{Done, Reply};
+
+/// GET /api/schema: JSON Schema for what `/ws` clients send and receive, generated from the serde types
+pub async fn schema_route() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "protocol": PROTOCOL_VERSION,
+        "client_command": schema_for!(ClientRequest),
+        "system_event": schema_for!(SystemEvent),
+        // v1 answers to a command sent with an `id`
+        "reply": schema_for!(Reply<'static>),
+        "done": schema_for!(Done<'static>),
+    }))
+}
@@ -0,0 +1,5 @@
+mod parser;
+mod runner;
+
+pub use parser::parse_duration_ms;
+pub use runner::{handle_run, run_route};
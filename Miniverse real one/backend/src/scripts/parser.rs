@@ -0,0 +1,257 @@
+use crate::config::ScriptsConfig;
+use crate::shell::Quoting;
+
+/// One parsed script statement. `line` is 1-based and points into the source text.
+#[derive(Debug, Clone)]
+pub enum Step {
+    Command { line: usize, text: String },
+    Sleep { ms: u64 },
+    Repeat { times: u32, body: Vec<Step> },
+}
+
+/// A step of a script with its repeats expanded, in run order
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlatStep {
+    Command { line: usize, text: String },
+    Sleep { ms: u64 },
+}
+
+/// Parse a script: one command per line (or `;`-separated), `# comments`,
+/// `sleep <ms|Ns>` and `repeat N { ... }` blocks, which may nest.
+pub fn parse(src: &str) -> Result<Vec<Step>, String> {
+    let mut iter = split_statements(src).into_iter();
+    parse_block(&mut iter, None)
+}
+
+/// Expand repeats into a flat list of steps, refusing scripts longer than `max_steps`
+/// or sleeping longer than `max_sleep_ms` in total
+pub fn flatten(steps: &[Step], limits: &ScriptsConfig) -> Result<Vec<FlatStep>, String> {
+    let mut flat = Flattener { out: Vec::new(), work: 0, slept_ms: 0, limits };
+    flat.expand(steps)?;
+    Ok(flat.out)
+}
+
+/// Accept `500`, `500ms`, `2s` or `1m`; None when malformed or too large
+pub fn parse_duration_ms(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+    if let Some(v) = s.strip_suffix("ms") {
+        v.parse().ok()
+    } else if let Some(v) = s.strip_suffix('s') {
+        v.parse::<u64>().ok().and_then(|v| v.checked_mul(1000))
+    } else if let Some(v) = s.strip_suffix('m') {
+        v.parse::<u64>().ok().and_then(|v| v.checked_mul(60_000))
+    } else {
+        s.parse().ok()
+    }
+}
+
+struct Flattener<'a> {
+    out: Vec<FlatStep>,
+    // Steps emitted plus repeat iterations, so empty repeats still use up the budget
+    work: usize,
+    slept_ms: u64,
+    limits: &'a ScriptsConfig,
+}
+
+impl Flattener<'_> {
+    fn tick(&mut self) -> Result<(), String> {
+        self.work += 1;
+        match self.work > self.limits.max_steps {
+            true => Err(format!("Script expands to more than {} steps", self.limits.max_steps)),
+            false => Ok(()),
+        }
+    }
+
+    fn expand(&mut self, steps: &[Step]) -> Result<(), String> {
+        for step in steps {
+            self.tick()?;
+            match step {
+                Step::Repeat { body, times } => {
+                    for _ in 0..*times {
+                        self.tick()?;
+                        self.expand(body)?;
+                    }
+                }
+                Step::Sleep { ms } => {
+                    self.slept_ms = self.slept_ms.saturating_add(*ms);
+                    if self.slept_ms > self.limits.max_sleep_ms {
+                        return Err(format!("Script sleeps for more than {} ms in total", self.limits.max_sleep_ms));
+                    }
+                    self.out.push(FlatStep::Sleep { ms: *ms });
+                }
+                Step::Command { line, text } => self.out.push(FlatStep::Command { line: *line, text: text.clone() }),
+            }
+        }
+        Ok(())
+    }
+}
+
+type Stmts = std::vec::IntoIter<(usize, String)>;
+
+fn parse_block(iter: &mut Stmts, opened_at: Option<usize>) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    while let Some((line, stmt)) = iter.next() {
+        let mut words = stmt.split_whitespace();
+        let head = words.next().unwrap_or("").to_lowercase();
+        match head.as_str() {
+            "}" => {
+                return match opened_at {
+                    Some(_) => Ok(steps),
+                    None => Err(format!("line {}: unexpected '}}'", line)),
+                };
+            }
+            "{" => return Err(format!("line {}: '{{' must follow 'repeat N'", line)),
+            "sleep" => {
+                let ms = words
+                    .next()
+                    .and_then(parse_duration_ms)
+                    .ok_or_else(|| format!("line {}: usage: sleep <ms|Ns>", line))?;
+                steps.push(Step::Sleep { ms });
+            }
+            "repeat" => {
+                let times = words
+                    .next()
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .ok_or_else(|| format!("line {}: usage: repeat N {{ ... }}", line))?;
+                match iter.next() {
+                    Some((_, open)) if open == "{" => {}
+                    _ => return Err(format!("line {}: expected '{{' after 'repeat {}'", line, times)),
+                }
+                let body = parse_block(iter, Some(line))?;
+                steps.push(Step::Repeat { times, body });
+            }
+            // No nesting, so the role check in the runner covers every step that runs
+            "run" => return Err(format!("line {}: scripts cannot call 'run'", line)),
+            _ => steps.push(Step::Command { line, text: stmt }),
+        }
+    }
+    match opened_at {
+        Some(line) => Err(format!("line {}: missing '}}' for repeat", line)),
+        None => Ok(steps),
+    }
+}
+
+/// Split source into (line, statement) pairs. Newlines and `;` end a statement and
+/// `{` / `}` stand alone; `;` and braces inside quotes or escaped are text, as in the terminal.
+fn split_statements(src: &str) -> Vec<(usize, String)> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut line = 1;
+    let mut start_line = 1;
+    let mut quoting = Quoting::default();
+
+    let flush = |cur: &mut String, at: usize, out: &mut Vec<(usize, String)>| {
+        let stmt = cur.trim();
+        if !stmt.is_empty() && !stmt.starts_with('#') {
+            out.push((at, stmt.to_string()));
+        }
+        cur.clear();
+    };
+
+    for ch in src.chars() {
+        if cur.trim().is_empty() {
+            start_line = line;
+        }
+        // Quotes end with the line; the tokenizer reports any left open
+        if ch == '\n' {
+            quoting = Quoting::default();
+            flush(&mut cur, start_line, &mut out);
+            line += 1;
+            continue;
+        }
+        // Quotes and escapes stay in the text; the tokenizer removes them later
+        let bare = quoting.bare(ch);
+        match ch {
+            ';' if bare => flush(&mut cur, start_line, &mut out),
+            '{' | '}' if bare => {
+                flush(&mut cur, start_line, &mut out);
+                out.push((line, ch.to_string()));
+            }
+            _ => cur.push(ch),
+        }
+    }
+    flush(&mut cur, start_line, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_steps: usize) -> ScriptsConfig {
+        ScriptsConfig { dir: String::new(), max_steps, max_sleep_ms: 10_000 }
+    }
+
+    fn flat(src: &str, max_steps: usize) -> Result<Vec<FlatStep>, String> {
+        flatten(&parse(src)?, &limits(max_steps))
+    }
+
+    #[test]
+    fn expands_nested_repeats_in_order() {
+        let steps = flat("repeat 2 { led on; repeat 2 { temp } }\nsleep 1s", 100).unwrap();
+        let texts: Vec<String> = steps
+            .iter()
+            .map(|s| match s {
+                FlatStep::Command { text, .. } => text.clone(),
+                FlatStep::Sleep { ms } => format!("sleep {}", ms),
+            })
+            .collect();
+        assert_eq!(texts, ["led on", "temp", "temp", "led on", "temp", "temp", "sleep 1000"]);
+    }
+
+    #[test]
+    fn huge_empty_repeat_hits_the_step_limit() {
+        let err = flat("repeat 4294967295 { }", 50).unwrap_err();
+        assert!(err.contains("more than 50 steps"), "{}", err);
+    }
+
+    #[test]
+    fn nested_empty_repeats_hit_the_step_limit() {
+        assert!(flat("repeat 4294967295 { repeat 4294967295 { } }", 50).is_err());
+    }
+
+    #[test]
+    fn step_limit_counts_expanded_commands() {
+        assert!(flat("repeat 10 { temp }", 100).is_ok());
+        assert!(flat("repeat 100 { temp }", 100).is_err());
+    }
+
+    #[test]
+    fn total_sleep_is_capped() {
+        assert!(flat("sleep 10s", 100).is_ok());
+        let err = flat("repeat 3 { sleep 5s }", 100).unwrap_err();
+        assert!(err.contains("10000 ms"), "{}", err);
+    }
+
+    #[test]
+    fn durations_parse_and_overflow_is_rejected() {
+        assert_eq!(parse_duration_ms("500"), Some(500));
+        assert_eq!(parse_duration_ms("250ms"), Some(250));
+        assert_eq!(parse_duration_ms("2s"), Some(2000));
+        assert_eq!(parse_duration_ms("1m"), Some(60_000));
+        assert_eq!(parse_duration_ms("18446744073709551615s"), None);
+        assert_eq!(parse_duration_ms("18446744073709551615m"), None);
+        assert_eq!(parse_duration_ms("abc"), None);
+    }
+
+    #[test]
+    fn separators_inside_quotes_are_text() {
+        let texts = |src: &str| -> Vec<String> {
+            split_statements(src).into_iter().map(|(_, s)| s).collect()
+        };
+        assert_eq!(texts("lcd show 'a;b'; temp"), ["lcd show 'a;b'", "temp"]);
+        assert_eq!(texts("lcd show '{x}' \"}\"\ntemp"), ["lcd show '{x}' \"}\"", "temp"]);
+        // A double quote inside single quotes does not open a string
+        assert_eq!(texts("lcd show 'say \"hi'; temp"), ["lcd show 'say \"hi'", "temp"]);
+        assert_eq!(texts("lcd show a\\;b; temp"), ["lcd show a\\;b", "temp"]);
+        // An unclosed quote ends with its line
+        assert_eq!(texts("lcd show 'oops\ntemp"), ["lcd show 'oops", "temp"]);
+        assert!(parse("repeat 2 { lcd show '}' }").is_ok());
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces() {
+        assert!(parse("repeat 2 { temp").is_err());
+        assert!(parse("temp }").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::auth::{command_role, Identity, Role};
+use crate::events::SystemEvent;
+use crate::scripts::parser::{self, FlatStep, Step};
+use crate::serial::handle_serial_command_with_transport;
+use crate::shell::{Flag, Invocation, Spec};
+use crate::state::{AppState, Transport};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub line: usize,
+    pub command: String,
+    pub ok: bool,
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptReport {
+    pub script: String,
+    pub executed: usize,
+    pub failed: usize,
+    // True when stop-on-error cut the run short
+    pub stopped: bool,
+    pub results: Vec<StepResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunScriptRequest {
+    // Inline script text; takes precedence over `name`
+    pub script: Option<String>,
+    // File in the configured scripts directory
+    pub name: Option<String>,
+    #[serde(default = "default_stop_on_error")]
+    pub stop_on_error: bool,
+    pub transport: Option<String>,
+}
+
+fn default_stop_on_error() -> bool { true }
+
+/// Terminal entry point: `run [--keep-going] <name | inline; commands>`
+pub async fn handle_run(inv: &Invocation, state: &AppState, transport: Option<Transport>, identity: &Identity) -> SystemEvent {
+    let (name, source, stop_on_error) = match resolve_args(inv, state) {
+        Ok(resolved) => resolved,
+        Err(e) => return SystemEvent::Error { source: "script".to_string(), message: e },
+    };
+    // Checked against the text read above, which is exactly what runs
+    if let Err(message) = check_role(&source, identity) {
+        return SystemEvent::Error { source: "auth".to_string(), message };
+    }
+
+    match run_source(&name, &source, state, transport, stop_on_error, identity).await {
+        Ok(report) if report.failed == 0 => SystemEvent::Output {
+            content: format!("Script '{}': {} steps ok", report.script, report.executed),
+        },
+        Ok(report) => SystemEvent::Error {
+            source: "script".to_string(),
+            message: format!(
+                "Script '{}': {} of {} steps failed{}",
+                report.script,
+                report.failed,
+                report.executed,
+                if report.stopped { " (stopped at first error)" } else { "" }
+            ),
+        },
+        Err(e) => SystemEvent::Error { source: "script".to_string(), message: e },
+    }
+}
+
+/// The script needs at least operator, or more if a step does
+fn check_role(source: &str, identity: &Identity) -> Result<(), String> {
+    let needed = source_commands(source)
+        .iter()
+        .map(|c| command_role(c))
+        .fold(Role::Operator, Role::max);
+    if identity.role < needed {
+        return Err(format!("This script requires the {} role (you are {})", needed, identity.role));
+    }
+    Ok(())
+}
+
+fn source_commands(source: &str) -> Vec<String> {
+    fn collect(steps: &[Step], out: &mut Vec<String>) {
+        for step in steps {
+            match step {
+                Step::Command { text, .. } => out.push(text.clone()),
+                Step::Repeat { body, .. } => collect(body, out),
+                Step::Sleep { .. } => {}
+            }
+        }
+    }
+    let mut out = Vec::new();
+    if let Ok(steps) = parser::parse(source) {
+        collect(&steps, &mut out);
+    }
+    out
+}
+
+const RUN: Spec = Spec::new("run [--keep-going] <name | cmd; cmd; ...>", 1, None)
+    .flags(&[Flag { long: "keep-going", short: Some('k'), value: None }])
+    .options_first();
+
+/// Split `[--keep-going] <name | inline; commands>` into (name, source, stop_on_error)
+fn resolve_args(inv: &Invocation, state: &AppState) -> Result<(String, String, bool), String> {
+    let args = inv.args(&RUN)?;
+    let stop_on_error = !args.flag("keep-going");
+    let rest = args.rest(0);
+
+    // A single bare word is a script file, anything else is inline
+    if args.len() > 1 || rest.contains(char::is_whitespace) || rest.contains(';') {
+        Ok(("inline".to_string(), rest, stop_on_error))
+    } else {
+        load_script(state, &rest).map(|src| (rest.clone(), src, stop_on_error)).map_err(|e| args.error_at(0, &e))
+    }
+}
+
+/// POST /api/scripts/run
+pub async fn run_route(
+    body: web::Json<RunScriptRequest>,
+    identity: web::ReqData<Identity>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let req = body.into_inner();
+    let state = state.get_ref();
+    let (name, source) = match (req.script, req.name) {
+        (Some(src), name) => (name.unwrap_or_else(|| "inline".to_string()), src),
+        (None, Some(name)) => match load_script(state, &name) {
+            Ok(src) => (name, src),
+            Err(e) => return HttpResponse::NotFound().json(serde_json::json!({ "error": e })),
+        },
+        (None, None) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Provide 'script' or 'name'" }));
+        }
+    };
+    if let Err(e) = check_role(&source, &identity) {
+        return HttpResponse::Forbidden().json(serde_json::json!({ "error": e }));
+    }
+    let transport = match req.transport.as_deref().map(str::to_lowercase).as_deref() {
+        Some("serial") => Some(Transport::Serial),
+        Some("mqtt") => Some(Transport::Mqtt),
+        Some(other) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("Unknown transport '{}'", other) }));
+        }
+        None => None,
+    };
+
+    match run_source(&name, &source, state, transport, req.stop_on_error, &identity).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    }
+}
+
+async fn run_source(
+    name: &str,
+    source: &str,
+    state: &AppState,
+    transport: Option<Transport>,
+    stop_on_error: bool,
+    identity: &Identity,
+) -> Result<ScriptReport, String> {
+    let steps = parser::parse(source)?;
+    let steps = parser::flatten(&steps, &state.config.scripts)?;
+    log::info!("Running script '{}' ({} steps)", name, steps.len());
+
+    let mut report = ScriptReport { script: name.to_string(), executed: 0, failed: 0, stopped: false, results: Vec::new() };
+    for step in steps {
+        match step {
+            FlatStep::Sleep { ms } => tokio::time::sleep(std::time::Duration::from_millis(ms)).await,
+            FlatStep::Command { line, text } => {
+                // Boxed: the command dispatcher is what called us via `run`
+                let event = Box::pin(handle_serial_command_with_transport(&text, state, transport, identity)).await;
+                let (ok, output) = match &event {
+                    SystemEvent::Output { content } => (true, content.clone()),
+                    SystemEvent::Error { message, .. } => (false, message.clone()),
+                    other => (true, serde_json::to_string(other).unwrap_or_default()),
+                };
+                state.broadcast(SystemEvent::ScriptStep {
+                    script: name.to_string(),
+                    user: identity.user.clone(),
+                    line,
+                    command: text.clone(),
+                    ok,
+                    output: output.clone(),
+                });
+                report.executed += 1;
+                report.results.push(StepResult { line, command: text, ok, output });
+                if !ok {
+                    report.failed += 1;
+                    if stop_on_error {
+                        report.stopped = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    state.broadcast(SystemEvent::ScriptFinished {
+        script: report.script.clone(),
+        executed: report.executed,
+        failed: report.failed,
+        stopped: report.stopped,
+    });
+    Ok(report)
+}
+
+fn load_script(state: &AppState, name: &str) -> Result<String, String> {
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("Invalid script name '{}'", name));
+    }
+    let dir = PathBuf::from(&state.config.scripts.dir);
+    for candidate in [dir.join(name), dir.join(format!("{}.mv", name))] {
+        if candidate.is_file() {
+            return std::fs::read_to_string(&candidate)
+                .map_err(|e| format!("Read {} failed: {}", candidate.display(), e));
+        }
+    }
+    Err(format!("Script '{}' not found in {}", name, dir.display()))
+}
@@ -1,17 +1,23 @@
-use serialport::{SerialPort, SerialPortType, UsbPortInfo};
+use serialport::{ClearBuffer, SerialPort, SerialPortType, UsbPortInfo};
 use serde::{Serialize, Deserialize};
-use serde::Serialize;
 use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+// Line prefixes that only a board talking at the right baud rate would send back
+const PROBE_REPLY_PREFIXES: &[&str] = &["VERSION:", "OK", "ERROR:", "ABOUT:", "CMDS:"];
+
 struct SendableSerialPort(Box<dyn SerialPort>);
 unsafe impl Send for SendableSerialPort {}
 
+/// Clones share the open port, so blocking I/O can run on a clone without holding `AppState::serial`
+#[derive(Clone)]
 pub struct SerialBridge {
     port: Option<Arc<Mutex<SendableSerialPort>>>,
     port_name: Option<String>,
     board_name: Option<String>,
+    // Topic board id learned from the handshake; see `board_id`
+    board_id: Option<String>,
     baud_rate: u32,
 }
 
@@ -21,7 +27,8 @@ impl SerialBridge {
             port: None,
             port_name: None,
             board_name: None,
-            baud_rate: 9600,
+            board_id: None,
+            baud_rate: 115200,
         }
     }
     
@@ -32,15 +39,20 @@ impl SerialBridge {
                     .iter()
                     .enumerate()
                     .map(|(idx, p)| {
-                        let board_name = match &p.port_type {
-                            SerialPortType::UsbPort(info) => Self::detect_board_name(info),
-                            _ => "Unknown Device".to_string(),
+                        let (board_name, usb) = match &p.port_type {
+                            SerialPortType::UsbPort(info) => (Self::detect_board_name(info), Some(info)),
+                            _ => ("Unknown Device".to_string(), None),
                         };
                         
                         PortInfo {
                             index: idx,
                             port_name: p.port_name.clone(),
                             board_name,
+                            manufacturer: usb.and_then(|u| u.manufacturer.clone()),
+                            product: usb.and_then(|u| u.product.clone()),
+                            serial_number: usb.and_then(|u| u.serial_number.clone()),
+                            vid: usb.map(|u| u.vid),
+                            pid: usb.map(|u| u.pid),
                         }
                     })
                     .collect()
@@ -108,16 +120,85 @@ impl SerialBridge {
         self.port = Some(Arc::new(Mutex::new(SendableSerialPort(port))));
         self.port_name = Some(port_name.to_string());
         self.board_name = Some(board_name.clone());
+        self.board_id = None;
         self.baud_rate = baud_rate;
         
         log::info!("Serial connected: {} ({}) @ {}", port_name, board_name, baud_rate);
         Ok(())
     }
     
+    /// Change the rate of the open port in place (no reopen, so the board is not reset)
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), String> {
+        let port = self.port.as_ref().ok_or("Not connected")?;
+        port.lock().unwrap()
+            .0
+            .set_baud_rate(baud_rate)
+            .map_err(|e| format!("Set baud rate failed: {}", e))?;
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    /// Send `probe` and report whether a well-formed reply comes back before the timeout.
+    /// Garbled (non UTF-8) lines are skipped, which is what a wrong baud rate produces.
+    pub fn probe(&self, probe: &str, timeout_ms: u64) -> bool {
+        if let Some(port) = self.port.as_ref() {
+            let _ = port.lock().unwrap().0.clear(ClearBuffer::Input);
+        }
+        if self.send_command(probe).is_err() {
+            return false;
+        }
+
+        let start = std::time::Instant::now();
+        let mut resent = false;
+        while let Some(left) = timeout_ms.checked_sub(start.elapsed().as_millis() as u64) {
+            // Boards that reset on open may miss the first probe while booting
+            if !resent && start.elapsed().as_millis() as u64 > timeout_ms / 2 {
+                resent = true;
+                let _ = self.send_command(probe);
+            }
+            match self.read_line(left.min(timeout_ms / 2).max(1)) {
+                Ok(line) => {
+                    let line = line.trim();
+                    if PROBE_REPLY_PREFIXES.iter().any(|p| line.starts_with(p)) {
+                        return true;
+                    }
+                }
+                Err(e) if e.starts_with("Read error") => return false,
+                Err(_) => continue,
+            }
+        }
+        false
+    }
+
+    /// Send `cmd` and collect reply lines until `done` matches one or the timeout elapses
+    pub fn query(&self, cmd: &str, done: impl Fn(&str) -> bool, timeout_ms: u64) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.send_command(cmd).is_err() {
+            return lines;
+        }
+        let start = std::time::Instant::now();
+        while let Some(left) = timeout_ms.checked_sub(start.elapsed().as_millis() as u64) {
+            match self.read_line(left.max(1)) {
+                Ok(line) => {
+                    let line = line.trim().to_string();
+                    let finished = done(&line);
+                    lines.push(line);
+                    if finished {
+                        break;
+                    }
+                }
+                Err(e) if e.starts_with("UTF-8") => continue,
+                Err(_) => break,
+            }
+        }
+        lines
+    }
+
     pub fn disconnect(&mut self) {
         self.port = None;
         self.port_name = None;
         self.board_name = None;
+        self.board_id = None;
         log::info!("Serial disconnected");
     }
     
@@ -133,6 +214,22 @@ impl SerialBridge {
         self.board_name.as_deref()
     }
     
+    /// Id of the connected board as used in MQTT topics (`miniverse/<id>/...`), which also keys
+    /// `AppState::boards`. Falls back to the USB board name until the handshake names the board.
+    pub fn board_id(&self) -> String {
+        if let Some(id) = &self.board_id {
+            return id.clone();
+        }
+        let raw = self.board_name.as_deref().unwrap_or("board1");
+        let mut s = raw.to_lowercase().replace(' ', "_");
+        if s.is_empty() { s = "board1".into(); }
+        s
+    }
+
+    pub fn set_board_id(&mut self, id: String) {
+        self.board_id = Some(id);
+    }
+
     pub fn get_baud_rate(&self) -> u32 {
         self.baud_rate
     }
@@ -177,10 +274,20 @@ impl SerialBridge {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortInfo {
     pub index: usize,
     pub port_name: String,
     pub board_name: String,
+    // USB descriptor details, shown by `scan`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vid: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u16>,
 }
@@ -1,349 +1,668 @@
-use crate::events::{SystemEvent, SensorDetail};
+use crate::alerts;
+use crate::audit;
+use crate::auth::{self, Access, Identity};
+use crate::events::SystemEvent;
+use crate::firmware::{self, FirmwareInfo};
+use crate::history;
+use crate::readings;
+use crate::rules;
+use crate::scheduler;
+use crate::scripts;
+use crate::sessions;
+use crate::shell::registry::{self, ready, Arg, CommandDef, Ctx, Mode, Via};
+use crate::shell::{Flag, Invocation, Spec};
 use crate::state::{AppState, Transport};
+use crate::serial::bridge::PortInfo;
 use crate::serial::SerialBridge;
 
 #[allow(dead_code)]
 pub async fn handle_serial_command(cmd: &str, state: &AppState) -> SystemEvent {
-    handle_serial_command_with_transport(cmd, state, None).await
+    handle_serial_command_with_transport(cmd, state, None, &Identity::system()).await
 }
 
 /// Session-aware handler: when `transport_override` is provided, it will be used
-/// to route unknown device commands instead of the global AppState transport.
+/// to route device commands instead of the global AppState transport.
 pub async fn handle_serial_command_with_transport(
     cmd: &str,
     state: &AppState,
     transport_override: Option<Transport>,
+    identity: &Identity,
 ) -> SystemEvent {
-    let parts: Vec<&str> = cmd.split_whitespace().collect();
-    let command = parts.first().copied().unwrap_or("");
-    
-    match command {
-        // Config mode utilities
-        "ports" => handle_ports().await,
-        "connect" => handle_connect(&parts[1..], state).await,
-        "disconnect" => handle_disconnect(state).await,
-        "status" => handle_status(state).await,
-        "transport" => handle_transport(&parts[1..], state).await,
-        // MQTT utilities
-        "mqtt" => handle_mqtt(&parts[1..], state).await,
-        // Info and meta
-        "info" => handle_info(state).await,
-        "help" => SystemEvent::Output { content: backend_help_text() },
-        "about" => SystemEvent::Output { content: "Miniverse Arduino Firmware - Physical Computing & IoT".to_string() },
-        "version" => SystemEvent::Output { content: "Miniverse Firmware: v1.0.0".to_string() },
-        // Device commands (Normal mode)
-        "temp" => exec_temp(&parts[1..], state, transport_override).await,
-        "distance" => exec_distance(&parts[1..], state, transport_override).await,
-    "set" => exec_set(&parts[1..], state, transport_override).await,
-    // accept both 'light' and 'led' as aliases
-    "light" | "led" => exec_light(&parts[1..], state, transport_override).await,
-        "lcd" => exec_lcd(&parts[1..], state, transport_override).await,
-        // Reject unknowns explicitly
-        _ => SystemEvent::Error { source: "cli".to_string(), message: "Unknown command. Type 'help' for available commands.".to_string() },
-    }
-}
-
-fn backend_help_text() -> String {
-    let mut s = String::new();
-    s.push_str("\n+------------------------------ HELP ------------------------------+\n");
-    s.push_str("| System                 | help, clear, config, normal/exit       |\n");
-    s.push_str("| Serial (config mode)   | ports, connect <n> [baud],             |\n");
-    s.push_str("|                        | disconnect, status                     |\n");
-    s.push_str("| Device (normal mode)   | temp, distance [id]                   |\n");
-    s.push_str("| LED                    | light on/off, set light <0-255> [color]|\n");
-    s.push_str("| LCD                    | lcd clear, lcd show \"a\" [\"b\"]     |\n");
-    s.push_str("| Firmware Meta          | help, version, about, info             |\n");
-    s.push_str("| Transport              | transport serial | transport mqtt      |\n");
-    s.push_str("| MQTT                   | mqtt sub <topic>, mqtt unsub <topic>,  |\n");
-    s.push_str("|                        | mqtt subs (list)                        |\n");
-    s.push_str("+----------------------------------------------------------------+\n");
-    s
-}
-
-async fn handle_transport(args: &[&str], state: &AppState) -> SystemEvent {
-    let mode = args.get(0).copied().unwrap_or("");
-    match mode.to_lowercase().as_str() {
-        "serial" => {
-            let mut t = state.transport.write().await;
-            *t = Transport::Serial;
-                // Notify UI
-                state.broadcast(crate::events::SystemEvent::TransportChanged {
-                    transport: "serial".to_string(),
-                    publish_topic: "".to_string(),
-                    subscribe_topics: vec![],
-                    board_id: {
-                        let serial = state.serial.read().await;
-                        Some(board_id_from_name(serial.get_board_name()))
-                    },
-                });
-            SystemEvent::Output { content: "Transport: serial".to_string() }
-        }
-        "mqtt" => {
-            let mut t = state.transport.write().await;
-            *t = Transport::Mqtt;
-                // Compute topics from state (initialized with config defaults)
-                let publish_topic = "miniverse/command".to_string();
-                let subscribe_topics = state.mqtt_topics.read().await.clone();
-                state.broadcast(crate::events::SystemEvent::TransportChanged {
-                    transport: "mqtt".to_string(),
-                    publish_topic: publish_topic.clone(),
-                    subscribe_topics: subscribe_topics.clone(),
-                    board_id: {
-                        let serial = state.serial.read().await;
-                        Some(board_id_from_name(serial.get_board_name()))
-                    },
-                });
-            SystemEvent::Output { content: "Transport: mqtt".to_string() }
+    let inv = match Invocation::parse(cmd) {
+        Ok(inv) => inv,
+        Err(message) => return cli_error(message),
+    };
+    let Some(def) = registry::find(COMMANDS, inv.name()) else {
+        return cli_error(inv.error_at_name(&format!("Unknown command '{}'. Type 'help' for available commands.", inv.name())));
+    };
+    if let Err(message) = registry::check_mode(def, sessions::mode_of(state, identity)) {
+        return SystemEvent::Error { source: "mode".to_string(), message };
+    }
+    if def.args.is_empty() {
+        if let Err(e) = inv.args(&Spec::new(def.usage[0], 0, Some(0))) {
+            return cli_error(e);
         }
-        _ => SystemEvent::Output { content: "Usage: transport <serial|mqtt>".to_string() },
     }
+    (def.handler)(&inv, Ctx { state, transport: transport_override, identity }).await
 }
 
-async fn handle_mqtt(args: &[&str], state: &AppState) -> SystemEvent {
-    let sub = args.get(0).copied().unwrap_or("");
-    match sub {
-        "sub" | "subscribe" => {
-            let topic = match args.get(1) { Some(t) => *t, None => {
-                return SystemEvent::Error { source: "mqtt".to_string(), message: "Usage: mqtt sub <topic>".to_string() };
-            }};
-            let mqtt = state.mqtt.read().await;
-            match mqtt.subscribe(topic).await {
-                Ok(_) => {
-                    // Track topic in state and notify UI to refresh topics list
-                    {
-                        let mut topics = state.mqtt_topics.write().await;
-                        if !topics.iter().any(|t| t == topic) {
-                            topics.push(topic.to_string());
-                        }
-                    }
-                    let current = state.mqtt_topics.read().await.clone();
-                    state.broadcast(crate::events::SystemEvent::TransportChanged {
-                        transport: "mqtt".to_string(),
-                        publish_topic: "miniverse/command".to_string(),
-                        subscribe_topics: current,
-                        board_id: {
-                            let serial = state.serial.read().await;
-                            Some(board_id_from_name(serial.get_board_name()))
-                        },
-                    });
-                    SystemEvent::Output { content: format!("MQTT: subscribed to {}", topic) }
-                },
-                Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
-            }
+/// Mode check for commands a WebSocket session intercepts before dispatch
+pub fn check_mode(inv: &Invocation, state: &AppState, identity: &Identity) -> Result<(), SystemEvent> {
+    match registry::find(COMMANDS, inv.name()) {
+        Some(def) => registry::check_mode(def, sessions::mode_of(state, identity))
+            .map_err(|message| SystemEvent::Error { source: "mode".to_string(), message }),
+        None => Ok(()),
+    }
+}
+
+/// Tab completion: full-line candidates for what the user has typed so far
+pub async fn complete_command(partial: &str, state: &AppState, identity: &Identity) -> Vec<String> {
+    registry::complete(partial, COMMANDS, state, identity).await
+}
+
+/// Bad arguments: the message already points at the offending word
+fn cli_error(message: String) -> SystemEvent {
+    SystemEvent::Error { source: "cli".to_string(), message }
+}
+
+// Intercepted by the WebSocket session before dispatch; anywhere else (scripts, REST, jobs, rules)
+// they have no session to act on, and `transport` must not switch every session-less caller
+fn session_only(inv: &Invocation) -> SystemEvent {
+    cli_error(inv.error_at_name(&format!("'{}' only works in an interactive terminal session", inv.name())))
+}
+
+const HELP: Spec = Spec::new("help [command]", 0, Some(1));
+
+fn handle_help(inv: &Invocation) -> SystemEvent {
+    let args = match inv.args(&HELP) { Ok(a) => a, Err(e) => return cli_error(e) };
+    match args.get(0) {
+        None => SystemEvent::Output {
+            content: format!("{}\nThe web terminal also handles 'clear' itself.\n", registry::help_text(COMMANDS)),
+        },
+        Some(name) => match registry::find(COMMANDS, &name.to_lowercase()) {
+            Some(def) => SystemEvent::Output { content: registry::command_help(def) },
+            None => cli_error(args.error_at(0, &format!("No such command '{}'. Type 'help' for the list.", name))),
+        },
+    }
+}
+
+/// Every terminal command. `help` and tab completion are generated from this table.
+static COMMANDS: &[CommandDef] = &[
+    // Session mode
+    CommandDef {
+        name: "config", aliases: &[], group: "System",
+        usage: &["config"], summary: "enter CONFIG mode (serial port and transport setup)",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |inv, cx| ready(handle_mode(inv, cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "exit", aliases: &["normal", "end"], group: "System",
+        usage: &["exit"], summary: "back to NORMAL mode (device commands)",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |inv, cx| ready(handle_mode(inv, cx.state, cx.identity)),
+    },
+    // Serial port (config mode)
+    CommandDef {
+        name: "ports", aliases: &[], group: "Serial",
+        usage: &["ports"], summary: "list serial ports",
+        mode: Mode::Config, via: Via::Serial, args: &[],
+        handler: |_, _| Box::pin(handle_ports()),
+    },
+    CommandDef {
+        name: "scan", aliases: &[], group: "Serial",
+        usage: &["scan"], summary: "list serial ports with USB manufacturer, product and VID:PID",
+        mode: Mode::Config, via: Via::Serial, args: &[],
+        handler: |_, _| ready(handle_scan()),
+    },
+    CommandDef {
+        name: "connect", aliases: &[], group: "Serial",
+        usage: &["connect <index> [baud|auto]", "connect <index> --baud <rate>", "connect <index> --auto"],
+        summary: "open a serial port; 'auto' probes the configured baud rates",
+        mode: Mode::Config, via: Via::Serial, args: &[Arg::Port, Arg::Baud],
+        handler: |inv, cx| Box::pin(handle_connect(inv, cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "disconnect", aliases: &[], group: "Serial",
+        usage: &["disconnect"], summary: "close the serial port",
+        mode: Mode::Config, via: Via::Serial, args: &[],
+        handler: |_, cx| Box::pin(handle_disconnect(cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "status", aliases: &[], group: "Serial",
+        usage: &["status"], summary: "serial connection, lock and queue",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |_, cx| Box::pin(handle_status(cx.state)),
+    },
+    CommandDef {
+        name: "lock", aliases: &[], group: "Serial",
+        usage: &["lock"], summary: "hold the serial port for this session only",
+        mode: Mode::Any, via: Via::Serial, args: &[],
+        handler: |inv, cx| ready(handle_lock(inv, cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "unlock", aliases: &[], group: "Serial",
+        usage: &["unlock [--force]"], summary: "release the serial lock; --force (admin) takes it from anyone",
+        mode: Mode::Any, via: Via::Serial, args: &[Arg::Words(&["force"])],
+        handler: |inv, cx| ready(handle_lock(inv, cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "transport", aliases: &[], group: "Serial",
+        usage: &["transport <serial|mqtt>"], summary: "route this session's device commands over serial or MQTT",
+        mode: Mode::Config, via: Via::Backend, args: &[Arg::Words(&["serial", "mqtt"])],
+        handler: |inv, _| ready(session_only(inv)),
+    },
+    // Device (normal mode)
+    CommandDef {
+        name: "temp", aliases: &[], group: "Device",
+        usage: &["temp"], summary: "read the temperature sensor",
+        mode: Mode::Normal, via: Via::Board, args: &[],
+        handler: |_, cx| Box::pin(exec_temp(cx.state, cx.transport, cx.identity)),
+    },
+    CommandDef {
+        name: "distance", aliases: &[], group: "Device",
+        usage: &["distance [id]"], summary: "read a distance sensor",
+        mode: Mode::Normal, via: Via::Board, args: &[Arg::Free],
+        handler: |inv, cx| Box::pin(exec_distance(inv, cx.state, cx.transport, cx.identity)),
+    },
+    CommandDef {
+        name: "light", aliases: &["led"], group: "Device",
+        usage: &["light <on|off>"], summary: "switch the LED",
+        mode: Mode::Normal, via: Via::Board, args: &[Arg::Words(&["on", "off"])],
+        handler: |inv, cx| Box::pin(exec_light(inv, cx.state, cx.transport, cx.identity)),
+    },
+    CommandDef {
+        name: "set", aliases: &[], group: "Device",
+        usage: &["set light <0-255> [color]"], summary: "set LED brightness and color",
+        mode: Mode::Normal, via: Via::Board, args: &[Arg::Words(&["light"]), Arg::Free, Arg::Free],
+        handler: |inv, cx| Box::pin(exec_set(inv, cx.state, cx.transport, cx.identity)),
+    },
+    CommandDef {
+        name: "lcd", aliases: &[], group: "Device",
+        usage: &["lcd clear", "lcd show \"<line1>\" [\"<line2>\"]"], summary: "clear or write the 16x2 LCD",
+        mode: Mode::Normal, via: Via::Board, args: &[Arg::Words(&["clear", "show"])],
+        handler: |inv, cx| Box::pin(exec_lcd(inv, cx.state, cx.transport, cx.identity)),
+    },
+    // Board meta
+    CommandDef {
+        name: "info", aliases: &[], group: "Board",
+        usage: &["info"], summary: "firmware version, sensors and commands",
+        mode: Mode::Any, via: Via::Board, args: &[],
+        handler: |_, cx| Box::pin(handle_info(cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "sysinfo", aliases: &["./info"], group: "System",
+        usage: &["sysinfo"], summary: "backend version, platform, uptime and connections",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |_, cx| Box::pin(handle_sysinfo(cx.state)),
+    },
+    CommandDef {
+        name: "about", aliases: &[], group: "Board",
+        usage: &["about"], summary: "about this firmware",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |_, cx| Box::pin(handle_version(cx.state, true)),
+    },
+    CommandDef {
+        name: "version", aliases: &[], group: "Board",
+        usage: &["version"], summary: "firmware version",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |_, cx| Box::pin(handle_version(cx.state, false)),
+    },
+    // MQTT
+    CommandDef {
+        name: "mqtt", aliases: &[], group: "MQTT",
+        usage: &["mqtt sub <topic>", "mqtt unsub <topic>", "mqtt subs", "mqtt pub <topic> [payload...]"],
+        summary: "subscriptions for this session, and publishing",
+        mode: Mode::Any, via: Via::Mqtt,
+        args: &[Arg::Words(&["sub", "unsub", "subs", "pub"]), Arg::BySub(&[("sub", Arg::Topic), ("unsub", Arg::OwnTopic), ("pub", Arg::Topic)])],
+        handler: |inv, cx| Box::pin(handle_mqtt(inv, cx.state, cx.identity)),
+    },
+    // Automation
+    CommandDef {
+        name: "run", aliases: &[], group: "Automation",
+        usage: &["run [--keep-going] <name>", "run [--keep-going] <cmd; cmd; ...>"], summary: "run a saved script or a list of commands",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Command],
+        handler: |inv, cx| Box::pin(scripts::handle_run(inv, cx.state, cx.transport, cx.identity)),
+    },
+    CommandDef {
+        name: "schedule", aliases: &[], group: "Automation",
+        usage: &["schedule add \"<cron>\" <command>", "schedule add @hourly <command>", "schedule list", "schedule rm <id>"],
+        summary: "run commands on a cron schedule",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Words(&["add", "list", "rm"]), Arg::Free, Arg::Command],
+        handler: |inv, cx| Box::pin(scheduler::handle_schedule(inv, cx.state)),
+    },
+    CommandDef {
+        name: "rule", aliases: &["rules"], group: "Automation",
+        usage: &["rule add if <cond> then <cmd>[; <cmd>]", "rule list", "rule rm <id>"],
+        summary: "react to readings and MQTT messages",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Words(&["add", "list", "rm"]), Arg::Free],
+        handler: |inv, cx| Box::pin(rules::handle_rule(inv, cx.state)),
+    },
+    CommandDef {
+        name: "alerts", aliases: &["alert"], group: "Automation",
+        usage: &["alerts [all|defs]", "alerts ack <id>", "alerts resolve <id>"], summary: "open alerts and their definitions",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Words(&["all", "defs", "ack", "resolve"]), Arg::Free],
+        handler: |inv, cx| Box::pin(alerts::handle_alerts(inv, cx.state)),
+    },
+    CommandDef {
+        name: "watch", aliases: &[], group: "Automation",
+        usage: &["watch", "watch <interval> <command>"], summary: "list watches, or repeat a command (e.g. watch 2s distance)",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Free, Arg::Command],
+        handler: |inv, _| ready(session_only(inv)),
+    },
+    CommandDef {
+        name: "unwatch", aliases: &[], group: "Automation",
+        usage: &["unwatch <id|all>"], summary: "stop a watch",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Free],
+        handler: |inv, _| ready(session_only(inv)),
+    },
+    // Session
+    CommandDef {
+        name: "whoami", aliases: &[], group: "Session",
+        usage: &["whoami"], summary: "your user, role and session",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |inv, _| ready(session_only(inv)),
+    },
+    CommandDef {
+        name: "who", aliases: &[], group: "Session",
+        usage: &["who"], summary: "connected sessions",
+        mode: Mode::Any, via: Via::Backend, args: &[],
+        handler: |_, cx| ready(sessions::handle_who(cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "nick", aliases: &[], group: "Session",
+        usage: &["nick [name | -]"], summary: "show, set or clear your nickname",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Free],
+        handler: |inv, _| ready(session_only(inv)),
+    },
+    CommandDef {
+        name: "history", aliases: &[], group: "Session",
+        usage: &["history [n]", "history clear", "!n | !-k | !!"], summary: "your recent commands, and recalling them",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Words(&["clear"])],
+        handler: |inv, cx| ready(history::handle_history(inv, cx.state, cx.identity)),
+    },
+    CommandDef {
+        name: "audit", aliases: &[], group: "Session",
+        usage: &["audit tail [n] [user]", "audit errors [n]"], summary: "what every session did (admin)",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Words(&["tail", "errors"]), Arg::Free],
+        handler: |inv, cx| ready(audit::handle_audit(inv, cx.state)),
+    },
+    CommandDef {
+        name: "help", aliases: &[], group: "Session",
+        usage: &["help [command]"], summary: "this list, or details for one command",
+        mode: Mode::Any, via: Via::Backend, args: &[Arg::Name],
+        handler: |inv, _| ready(handle_help(inv)),
+    },
+];
+
+const MQTT_USAGE: &str = "Usage:\n  mqtt sub <topic>\n  mqtt unsub <topic>\n  mqtt subs\n  mqtt pub <topic> <payload>\n";
+const MQTT_SUB: Spec = Spec::new("mqtt sub <topic>", 1, Some(1));
+const MQTT_UNSUB: Spec = Spec::new("mqtt unsub <topic>", 1, Some(1));
+const MQTT_SUBS: Spec = Spec::new("mqtt subs", 0, Some(0));
+// The payload is everything after the topic as typed (spacing and quotes kept); dashes in it are not options
+const MQTT_PUB: Spec = Spec::new("mqtt pub <topic> [payload...]", 1, None).options_first();
+
+async fn handle_mqtt(inv: &Invocation, state: &AppState, identity: &Identity) -> SystemEvent {
+    let Some(sub) = inv.subcommand() else {
+        return SystemEvent::Output { content: MQTT_USAGE.to_string() };
+    };
+    let (spec, access) = match sub.name() {
+        "sub" | "subscribe" => (MQTT_SUB, Some(Access::Subscribe)),
+        "unsub" | "unsubscribe" => (MQTT_UNSUB, Some(Access::Subscribe)),
+        "subs" | "list" | "ls" => (MQTT_SUBS, None),
+        "pub" | "publish" => (MQTT_PUB, Some(Access::Publish)),
+        other => return cli_error(format!("{}\n{}", sub.error_at_name(&format!("Unknown mqtt subcommand '{}'", other)), MQTT_USAGE)),
+    };
+    let args = match sub.args(&spec) { Ok(a) => a, Err(e) => return cli_error(e) };
+    let topic = args.get(0).unwrap_or("");
+    if let Some(access) = access {
+        if let Err(message) = auth::check_topic(identity, state, access, topic) {
+            return SystemEvent::Error { source: "acl".to_string(), message };
         }
-        "unsub" | "unsubscribe" => {
-            let topic = match args.get(1) { Some(t) => *t, None => {
-                return SystemEvent::Error { source: "mqtt".to_string(), message: "Usage: mqtt unsub <topic>".to_string() };
-            }};
-            let mqtt = state.mqtt.read().await;
-            match mqtt.unsubscribe(topic).await {
-                Ok(_) => {
-                    // Remove topic from state list if present
-                    {
-                        let mut topics = state.mqtt_topics.write().await;
-                        if let Some(pos) = topics.iter().position(|t| t == topic) {
-                            topics.remove(pos);
-                        }
-                    }
-                    let current = state.mqtt_topics.read().await.clone();
-                    state.broadcast(crate::events::SystemEvent::TransportChanged {
-                        transport: "mqtt".to_string(),
-                        publish_topic: "miniverse/command".to_string(),
-                        subscribe_topics: current,
-                        board_id: {
-                            let serial = state.serial.read().await;
-                            Some(board_id_from_name(serial.get_board_name()))
-                        },
-                    });
-                    SystemEvent::Output { content: format!("MQTT: unsubscribed from {}", topic) }
+    }
+    match sub.name() {
+        "sub" | "subscribe" => mqtt_subscribe(topic, state, identity).await,
+        "unsub" | "unsubscribe" => mqtt_unsubscribe(topic, state, identity).await,
+        "subs" | "list" | "ls" => {
+            let Ok(subs) = state.mqtt_subs.read() else {
+                return SystemEvent::Error { source: "mqtt".to_string(), message: "Subscription table unavailable".to_string() };
+            };
+            let list = |v: &[String]| if v.is_empty() { "  (none)".to_string() } else { v.iter().map(|t| format!("  {}", t)).collect::<Vec<_>>().join("\n") };
+            let mut out = format!("Global ({}):\n{}", subs.global().len(), list(subs.global()));
+            match identity.session {
+                Some(id) => {
+                    let own = subs.session(id);
+                    out.push_str(&format!("\nThis session ({}):\n{}", own.len(), list(&own)));
+                }
+                None => {
+                    let shared: Vec<String> = subs.session_filters().into_iter().map(|(f, n)| format!("{} ({} session(s))", f, n)).collect();
+                    out.push_str(&format!("\nSessions ({}):\n{}", shared.len(), list(&shared)));
                 }
-                Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
             }
+            SystemEvent::Output { content: out }
         }
-        "subs" => {
-            // List current subscriptions from state
-            let current = state.mqtt_topics.read().await.clone();
-            if current.is_empty() {
-                SystemEvent::Output { content: "MQTT: no subscriptions".to_string() }
-            } else {
-                SystemEvent::Output { content: format!("MQTT Subscriptions ({}):\n{}", current.len(), current.join("\n")) }
+        _ => mqtt_publish(topic, &args.rest(1), state).await,
+    }
+}
+
+/// WebSocket sessions own their subscriptions; scripts and REST callers change the global set,
+/// which every session sees, so that takes the admin role. Callers check the topic ACL first.
+pub(super) async fn mqtt_subscribe(topic: &str, state: &AppState, identity: &Identity) -> SystemEvent {
+    if let Err(e) = global_change_allowed(identity) {
+        return e;
+    }
+    match identity.session {
+        Some(id) => match state.update_subscriptions(|s| s.add(id, topic)).await {
+            Ok(()) => SystemEvent::Output { content: format!("MQTT: subscribed to {} (this session)", topic) },
+            Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
+        },
+        None => match state.update_subscriptions(|s| Ok(s.add_global(topic))).await {
+            Ok(()) => {
+                broadcast_global_topics(state).await;
+                SystemEvent::Output { content: format!("MQTT: subscribed to {}", topic) }
             }
-        }
-        "pub" | "publish" => {
-            let topic = match args.get(1) { Some(t) => *t, None => {
-                return SystemEvent::Error { source: "mqtt".to_string(), message: "Usage: mqtt pub <topic> <payload>".to_string() };
-            }};
-            let payload = if args.len() > 2 { args[2..].join(" ") } else { String::new() };
-            let mqtt = state.mqtt.read().await;
-            match mqtt.publish(topic, payload.as_bytes()).await {
-                Ok(_) => SystemEvent::Output { content: format!("MQTT: published to {}: {}", topic, payload) },
-                Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
+            Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
+        },
+    }
+}
+
+pub(super) async fn mqtt_unsubscribe(topic: &str, state: &AppState, identity: &Identity) -> SystemEvent {
+    if let Err(e) = global_change_allowed(identity) {
+        return e;
+    }
+    match identity.session {
+        Some(id) => match state.update_subscriptions(|s| s.remove(id, topic)).await {
+            Ok(()) => SystemEvent::Output { content: format!("MQTT: unsubscribed from {} (this session)", topic) },
+            Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
+        },
+        None => match state.update_subscriptions(|s| s.remove_global(topic)).await {
+            Ok(()) => {
+                broadcast_global_topics(state).await;
+                SystemEvent::Output { content: format!("MQTT: unsubscribed from {}", topic) }
             }
-        }
-        _ => SystemEvent::Output { content: "Usage:\n  mqtt sub <topic>\n  mqtt unsub <topic>\n  mqtt subs\n  mqtt pub <topic> <payload>\n".to_string() },
+            Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
+        },
     }
 }
 
-// ===== Device command executors =====
+fn global_change_allowed(identity: &Identity) -> Result<(), SystemEvent> {
+    match identity.session {
+        Some(_) => Ok(()),
+        None => identity
+            .allows(auth::Role::Admin, "Changing the global MQTT subscriptions")
+            .map_err(|message| SystemEvent::Error { source: "auth".to_string(), message }),
+    }
+}
 
-fn board_id_from_name(name: Option<&str>) -> String {
-    let raw = name.unwrap_or("board1");
-    let mut s = raw.to_lowercase().replace(' ', "_");
-    if s.is_empty() { s = "board1".into(); }
-    s
+pub(super) async fn mqtt_publish(topic: &str, payload: &str, state: &AppState) -> SystemEvent {
+    let mqtt = state.mqtt.read().await;
+    match mqtt.publish(topic, payload.as_bytes()).await {
+        Ok(_) => SystemEvent::Output { content: format!("MQTT: published to {}: {}", topic, payload) },
+        Err(e) => SystemEvent::Error { source: "mqtt".to_string(), message: e },
+    }
+}
+
+/// Tell every UI the global topic list changed
+async fn broadcast_global_topics(state: &AppState) {
+    let board_id = {
+        let serial = state.serial.read().await;
+        Some(serial.board_id())
+    };
+    state.broadcast(SystemEvent::TransportChanged {
+        transport: "mqtt".to_string(),
+        publish_topic: "miniverse/command".to_string(),
+        subscribe_topics: state.global_topics(),
+        board_id,
+    });
 }
 
-async fn publish_component_command(state: &AppState, component: &str, payload: &str) -> Result<(), String> {
+// ===== Device command executors =====
+
+async fn publish_component_command(state: &AppState, identity: &Identity, component: &str, payload: &str) -> Result<(), SystemEvent> {
     let serial = state.serial.read().await;
-    let bid = board_id_from_name(serial.get_board_name());
+    let bid = serial.board_id();
+    drop(serial);
     let topic = format!("miniverse/{}/{}/command", bid, component);
+    auth::check_topic(identity, state, Access::Publish, &topic).map_err(|message| SystemEvent::Error { source: "acl".to_string(), message })?;
     let mqtt = state.mqtt.read().await;
-    mqtt.publish(&topic, payload.as_bytes()).await
+    mqtt.publish(&topic, payload.as_bytes()).await.map_err(|message| SystemEvent::Error { source: "mqtt".to_string(), message })
+}
+
+/// Where a command line ends up: the MQTT topic for device commands over MQTT,
+/// the serial port otherwise, or nothing for commands handled by the backend itself
+pub async fn command_target(cmd: &str, state: &AppState, transport: Transport) -> String {
+    let head = cmd.split_whitespace().next().unwrap_or("").to_lowercase();
+    let component = match head.as_str() {
+        "temp" => "temp",
+        "distance" => "distance",
+        "set" | "light" | "led" => "led",
+        "lcd" => "lcd",
+        "info" => "info",
+        _ => return String::new(),
+    };
+    let serial = state.serial.read().await;
+    match transport {
+        Transport::Mqtt => component_topic_sync(&serial, component),
+        Transport::Serial => serial.get_port_name().unwrap_or("(no port)").to_string(),
+    }
 }
 
 fn component_topic_sync(serial: &crate::serial::SerialBridge, component: &str) -> String {
-    let bid = board_id_from_name(serial.get_board_name());
+    let bid = serial.board_id();
     format!("miniverse/{}/{}/command", bid, component)
 }
 
-async fn exec_temp(_args: &[&str], state: &AppState, transport_override: Option<Transport>) -> SystemEvent {
-    // Firmware chooses/display unit; send bare 'temp'
-    let payload = "temp".to_string();
+/// Send a device command to `board` (default: the one on the serial port) over the session's transport.
+/// Over serial only the connected board can be addressed; over MQTT any board id works.
+pub(super) async fn send_device(
+    state: &AppState,
+    transport_override: Option<Transport>,
+    identity: &Identity,
+    board: Option<&str>,
+    component: &str,
+    payload: &str,
+) -> SystemEvent {
+    let (serial_board, serial_connected) = {
+        let serial = state.serial.read().await;
+        (serial.board_id(), serial.is_connected())
+    };
+    let board = board.unwrap_or(&serial_board);
+    if let Err(e) = ensure_supported(state, Some(board), payload).await { return e; }
     match transport_override.unwrap_or(*state.transport.read().await) {
-        Transport::Serial => forward_to_arduino(&payload, state).await,
+        Transport::Serial if serial_connected && board != serial_board => SystemEvent::Error {
+            source: "serial".to_string(),
+            message: format!("'{}' is not the board on the serial port ({}); use the MQTT transport", board, serial_board),
+        },
+        Transport::Serial => forward_to_arduino(payload, state, identity).await,
         Transport::Mqtt => {
-            let serial = state.serial.read().await;
-            let topic = component_topic_sync(&serial, "temp");
-            drop(serial);
+            let topic = format!("miniverse/{}/{}/command", board, component);
+            // Whoever may not publish to the board's topics may not drive it either
+            if let Err(message) = auth::check_topic(identity, state, Access::Publish, &topic) {
+                return SystemEvent::Error { source: "acl".to_string(), message };
+            }
             let mqtt = state.mqtt.read().await;
             match mqtt.publish(&topic, payload.as_bytes()).await {
                 Ok(_) => SystemEvent::Output { content: format!("MQTT: sent {} - {} - ok", topic, payload) },
                 Err(e) => SystemEvent::Error { source: "mqtt".into(), message: e },
             }
-        },
+        }
     }
 }
 
-async fn exec_distance(args: &[&str], state: &AppState, transport_override: Option<Transport>) -> SystemEvent {
-    let payload = if let Some(id) = args.get(0) { format!("distance {}", id) } else { "distance".to_string() };
-    match transport_override.unwrap_or(*state.transport.read().await) {
-        Transport::Serial => forward_to_arduino(&payload, state).await,
-        Transport::Mqtt => {
-            let serial = state.serial.read().await;
-            let topic = component_topic_sync(&serial, "distance");
-            drop(serial);
-            let mqtt = state.mqtt.read().await;
-            match mqtt.publish(&topic, payload.as_bytes()).await {
-                Ok(_) => SystemEvent::Output { content: format!("MQTT: sent {} - {} - ok", topic, payload) },
-                Err(e) => SystemEvent::Error { source: "mqtt".into(), message: e },
-            }
-        },
-    }
+async fn exec_temp(state: &AppState, transport_override: Option<Transport>, identity: &Identity) -> SystemEvent {
+    // Firmware chooses/display unit; send bare 'temp'
+    send_device(state, transport_override, identity, None, "temp", "temp").await
 }
 
-async fn exec_set(args: &[&str], state: &AppState, transport_override: Option<Transport>) -> SystemEvent {
-    // support: set light <0-255> [color]
-    if args.get(0) != Some(&"light") {
-        return SystemEvent::Error { source: "cli".into(), message: "Usage: set light <0-255> [color]".into() };
-    }
-    let val = match args.get(1).and_then(|v| v.parse::<u16>().ok()) { Some(v) if v <= 255 => v, _ => {
-        return SystemEvent::Error { source: "cli".into(), message: "Usage: set light <0-255> [color]".into() };
-    }};
-    let color = args.get(2).copied();
-    let payload = match color { Some(c) => format!("set light {} {}", val, c), None => format!("set light {}", val) };
-    match transport_override.unwrap_or(*state.transport.read().await) {
-        Transport::Serial => forward_to_arduino(&payload, state).await,
-        Transport::Mqtt => {
-            let serial = state.serial.read().await;
-            let topic = component_topic_sync(&serial, "led");
-            drop(serial);
-            let mqtt = state.mqtt.read().await;
-            match mqtt.publish(&topic, payload.as_bytes()).await {
-                Ok(_) => SystemEvent::Output { content: format!("MQTT: sent {} - {} - ok", topic, payload) },
-                Err(e) => SystemEvent::Error { source: "mqtt".into(), message: e },
-            }
-        },
+const DISTANCE: Spec = Spec::new("distance [id]", 0, Some(1));
+
+async fn exec_distance(inv: &Invocation, state: &AppState, transport_override: Option<Transport>, identity: &Identity) -> SystemEvent {
+    let args = match inv.args(&DISTANCE) { Ok(a) => a, Err(e) => return cli_error(e) };
+    let payload = match args.get(0) { Some(id) => format!("distance {}", id), None => "distance".to_string() };
+    send_device(state, transport_override, identity, None, "distance", &payload).await
+}
+
+const SET: Spec = Spec::new("set light <0-255> [color]", 2, Some(3));
+
+async fn exec_set(inv: &Invocation, state: &AppState, transport_override: Option<Transport>, identity: &Identity) -> SystemEvent {
+    let args = match inv.args(&SET) { Ok(a) => a, Err(e) => return cli_error(e) };
+    if !args.get(0).is_some_and(|w| w.eq_ignore_ascii_case("light")) {
+        return cli_error(args.error_at(0, "Only 'set light' is supported"));
     }
+    let val = match args.require::<u8>(1, "brightness") { Ok(v) => v, Err(e) => return cli_error(e) };
+    send_device(state, transport_override, identity, None, "led", &light_payload(val, args.get(2))).await
+}
+
+/// What the firmware expects for an LED level, as `set light` and `light on|off` send it
+pub(super) fn light_payload(val: u8, color: Option<&str>) -> String {
+    match color { Some(c) => format!("set light {} {}", val, c), None => format!("set light {}", val) }
 }
 
-async fn exec_light(args: &[&str], state: &AppState, transport_override: Option<Transport>) -> SystemEvent {
-    let sub = args.get(0).copied().unwrap_or("");
-    match sub {
-        "on" => exec_set(&["light", "255"], state, transport_override).await,
-        "off" => exec_set(&["light", "0"], state, transport_override).await,
-        _ => SystemEvent::Error { source: "cli".into(), message: "Usage: light <on|off>".into() },
+const LIGHT: Spec = Spec::new("light <on|off>", 1, Some(1));
+
+async fn exec_light(inv: &Invocation, state: &AppState, transport_override: Option<Transport>, identity: &Identity) -> SystemEvent {
+    let args = match inv.args(&LIGHT) { Ok(a) => a, Err(e) => return cli_error(e) };
+    match args.get(0).unwrap_or("").to_lowercase().as_str() {
+        "on" => send_device(state, transport_override, identity, None, "led", &light_payload(255, None)).await,
+        "off" => send_device(state, transport_override, identity, None, "led", &light_payload(0, None)).await,
+        other => cli_error(args.error_at(0, &format!("Expected on or off, got '{}'", other))),
     }
 }
 
-async fn exec_lcd(args: &[&str], state: &AppState, transport_override: Option<Transport>) -> SystemEvent {
-    let sub = args.get(0).copied().unwrap_or("");
-    match sub {
+const LCD_USAGE: &str = "Usage: lcd clear | lcd show \"<line1>\" [\"<line2>\"]";
+const LCD_CLEAR: Spec = Spec::new("lcd clear", 0, Some(0));
+const LCD_SHOW: Spec = Spec::new("lcd show \"<line1>\" [\"<line2>\"]", 1, Some(2));
+// 16x2 character LCD
+const LCD_WIDTH: usize = 16;
+
+async fn exec_lcd(inv: &Invocation, state: &AppState, transport_override: Option<Transport>, identity: &Identity) -> SystemEvent {
+    let Some(sub) = inv.subcommand() else {
+        return cli_error(LCD_USAGE.to_string());
+    };
+    match sub.name() {
         "clear" => {
+            if let Err(e) = sub.args(&LCD_CLEAR) { return cli_error(e); }
             let payload = "lcd clear".to_string();
+            if let Err(e) = ensure_supported(state, None, &payload).await { return e; }
             match transport_override.unwrap_or(*state.transport.read().await) {
-                Transport::Serial => forward_to_arduino(&payload, state).await,
-                Transport::Mqtt => match publish_component_command(state, "lcd", &payload).await {
+                Transport::Serial => forward_to_arduino(&payload, state, identity).await,
+                Transport::Mqtt => match publish_component_command(state, identity, "lcd", &payload).await {
                     Ok(_) => SystemEvent::Output { content: "LCD: cleared".into() },
-                    Err(e) => SystemEvent::Error { source: "mqtt".into(), message: e },
+                    Err(e) => e,
                 },
             }
         }
         "show" => {
-            // Parse quoted lines: lcd show "line1" ["line2"]
-            let joined = args[1..].join(" ");
-            match parse_lcd_show_args(&joined) {
-                Ok((l1, l2)) => {
-                    // Optional length cap for 16x2 LCDs
-                    let line1 = if l1.len() > 16 { l1[..16].to_string() } else { l1 };
-                    let payload = if let Some(mut l2s) = l2 {
-                        if l2s.len() > 16 { l2s = l2s[..16].to_string(); }
-                        format!("lcd show \"{}\" \"{}\"", line1, l2s)
-                    } else {
-                        format!("lcd show \"{}\"", line1)
+            match sub.args(&LCD_SHOW) {
+                Ok(args) => {
+                    // Cut to the display width; the firmware parses the quotes back out
+                    let fit = |l: &str| -> String { l.chars().take(LCD_WIDTH).collect::<String>().replace('"', "'") };
+                    let payload = match args.get(1) {
+                        Some(l2) => format!("lcd show \"{}\" \"{}\"", fit(args.get(0).unwrap_or("")), fit(l2)),
+                        None => format!("lcd show \"{}\"", fit(args.get(0).unwrap_or(""))),
                     };
-                    match transport_override.unwrap_or(*state.transport.read().await) {
-                        Transport::Serial => forward_to_arduino(&payload, state).await,
-                        Transport::Mqtt => {
-                            let serial = state.serial.read().await;
-                            let topic = component_topic_sync(&serial, "lcd");
-                            drop(serial);
-                            let mqtt = state.mqtt.read().await;
-                            match mqtt.publish(&topic, payload.as_bytes()).await {
-                                Ok(_) => SystemEvent::Output { content: format!("MQTT: sent {} - {} - ok", topic, payload) },
-                                Err(e) => SystemEvent::Error { source: "mqtt".into(), message: e },
-                            }
-                        },
-                    }
+                    send_device(state, transport_override, identity, None, "lcd", &payload).await
                 }
-                Err(msg) => SystemEvent::Error { source: "cli".into(), message: msg },
+                Err(e) => cli_error(e),
+            }
+        }
+        other => cli_error(format!("{}\n{}", sub.error_at_name(&format!("Unknown lcd subcommand '{}'", other)), LCD_USAGE)),
+    }
+}
+
+/// `scan`: every port with its USB descriptor
+fn handle_scan() -> SystemEvent {
+    let ports = match SerialBridge::list_ports() {
+        Ok(ports) => ports,
+        Err(e) => return SystemEvent::Error { source: "serial".to_string(), message: format!("Failed to scan ports: {}", e) },
+    };
+    if ports.is_empty() {
+        return SystemEvent::Output { content: "No serial ports detected.".to_string() };
+    }
+    let mut out = String::from("Available serial ports:\n");
+    for p in ports {
+        out.push_str(&format!("\n[{}] {}\n    Device:       {}\n", p.index, p.port_name, p.board_name));
+        if let Some(m) = &p.manufacturer {
+            out.push_str(&format!("    Manufacturer: {}\n", m));
+        }
+        if let Some(product) = &p.product {
+            out.push_str(&format!("    Product:      {}\n", product));
+        }
+        if let Some(serial) = &p.serial_number {
+            out.push_str(&format!("    Serial:       {}\n", serial));
+        }
+        if let (Some(vid), Some(pid)) = (p.vid, p.pid) {
+            out.push_str(&format!("    VID:PID =     {:04X}:{:04X}\n", vid, pid));
+        }
+    }
+    out.push_str("\nconnect <index> [baud|auto]\n");
+    SystemEvent::Output { content: out }
+}
+
+/// `version` / `about`: what each board reported in its handshake, the serial board first
+async fn handle_version(state: &AppState, about: bool) -> SystemEvent {
+    let serial_board = state.serial.read().await.board_id();
+    let boards = state.boards.read().await;
+    let mut ids: Vec<&String> = boards.keys().filter(|id| boards[*id].is_known()).collect();
+    ids.sort_by_key(|id| (**id != serial_board, id.to_string()));
+    if ids.is_empty() {
+        return SystemEvent::Error {
+            source: "firmware".to_string(),
+            message: "No board has answered the firmware handshake yet; connect one or run 'info'".to_string(),
+        };
+    }
+    let lines: Vec<String> = ids
+        .into_iter()
+        .map(|id| {
+            let fw = &boards[id];
+            if about {
+                format!(
+                    "{}: {} running firmware {} ({} commands advertised)",
+                    id, fw.board.as_deref().unwrap_or("Unknown board"), fw.firmware_label(), fw.commands.len()
+                )
+            } else {
+                format!("{}: firmware {}", id, fw.version.clone().unwrap_or_else(|| fw.firmware_label()))
             }
+        })
+        .collect();
+    SystemEvent::Output { content: lines.join("\n") }
+}
+
+/// `sysinfo`: the backend itself, as opposed to `info` for the board
+async fn handle_sysinfo(state: &AppState) -> SystemEvent {
+    let uptime = chrono::Local::now() - state.started_at;
+    let serial = {
+        let serial = state.serial.read().await;
+        match serial.get_port_name() {
+            Some(port) => format!("{} @ {} baud", port, serial.get_baud_rate()),
+            None => "not connected".to_string(),
         }
-        _ => SystemEvent::Error { source: "cli".into(), message: "Usage: lcd <clear|show \"a\" [\"b\"]>".into() },
-    }
-}
-
-fn parse_lcd_show_args(input: &str) -> Result<(String, Option<String>), String> {
-    // Expect one or two quoted segments
-    let bytes = input.as_bytes();
-    let mut parts: Vec<String> = Vec::new();
-    let mut i = 0;
-    while i < bytes.len() {
-        // skip spaces
-        while i < bytes.len() && bytes[i].is_ascii_whitespace() { i += 1; }
-        if i >= bytes.len() { break; }
-        if bytes[i] != b'"' { return Err("Usage: lcd show \"a\" [\"b\"]".into()); }
-        i += 1; // skip opening quote
-        let start = i;
-        while i < bytes.len() && bytes[i] != b'"' { i += 1; }
-        if i >= bytes.len() { return Err("Missing closing quote".into()); }
-        let seg = &input[start..i];
-        parts.push(seg.to_string());
-        i += 1; // skip closing quote
-    }
-    if parts.is_empty() || parts.len() > 2 { return Err("Usage: lcd show \"a\" [\"b\"]".into()); }
-    let first = parts[0].clone();
-    let second = if parts.len() == 2 { Some(parts[1].clone()) } else { None };
-    Ok((first, second))
+    };
+    let transport = match *state.transport.read().await { Transport::Serial => "serial", Transport::Mqtt => "mqtt" };
+    let content = format!(
+        "System Information\n------------------\nBackend:   {} v{}\nPlatform:  {} ({})\nStarted:   {} (up {}h {:02}m)\nSessions:  {}\nTransport: {}\nSerial:    {}\nMQTT:      {}:{}\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        state.started_at.format("%Y-%m-%d %H:%M:%S"),
+        uptime.num_hours(),
+        uptime.num_minutes() % 60,
+        sessions::list(state).len(),
+        transport,
+        serial,
+        state.config.mqtt.broker_host,
+        state.config.mqtt.broker_port,
+    );
+    SystemEvent::Output { content }
+}
+
+/// `config` and `exit`: move this session between Normal and Config mode
+fn handle_mode(inv: &Invocation, state: &AppState, identity: &Identity) -> SystemEvent {
+    let requested = if inv.name() == "config" { "config" } else { "normal" };
+    match sessions::set_mode(state, identity, requested) {
+        Ok(mode) => SystemEvent::ModeChanged { mode: mode.name().to_string() },
+        Err(message) => SystemEvent::Error { source: "mode".to_string(), message },
+    }
 }
 
 async fn handle_ports() -> SystemEvent {
@@ -357,7 +676,7 @@ async fn handle_ports() -> SystemEvent {
                 output.push_str(&format!("[{}] {} ({})\n", p.index, p.port_name, p.board_name));
             }
             
-            output.push_str("\nconnect <index> [baud]\n");
+            output.push_str("\nconnect <index> [baud|auto]\n");
             
             SystemEvent::Output { content: output }
         }
@@ -371,102 +690,184 @@ async fn handle_ports() -> SystemEvent {
     }
 }
 
-async fn handle_connect(args: &[&str], state: &AppState) -> SystemEvent {
-    let index = args.get(0).and_then(|s| s.parse::<usize>().ok());
-    let baud = args.get(1).and_then(|s| s.parse::<u32>().ok()).unwrap_or(115200);
+const CONNECT: Spec = Spec::new("connect <index> [baud|auto] [--baud <rate>] [--auto]", 1, Some(2)).flags(&[
+    Flag { long: "baud", short: Some('b'), value: Some("baud rate") },
+    Flag { long: "auto", short: Some('a'), value: None },
+]);
+
+async fn handle_connect(inv: &Invocation, state: &AppState, identity: &Identity) -> SystemEvent {
+    let args = match inv.args(&CONNECT) { Ok(a) => a, Err(e) => return cli_error(e) };
+    let idx = match args.require::<usize>(0, "port index") { Ok(i) => i, Err(e) => return cli_error(e) };
+    let auto = args.flag("auto") || args.get(1).is_some_and(|s| s.eq_ignore_ascii_case("auto"));
+    let requested = match (args.parse_value::<u32>("baud", "baud rate"), auto) {
+        (Err(e), _) => return cli_error(e),
+        (Ok(Some(rate)), _) => Some(rate),
+        (Ok(None), true) => None,
+        (Ok(None), false) => match args.parse::<u32>(1, "baud rate") { Ok(rate) => rate, Err(e) => return cli_error(e) },
+    };
+    match SerialBridge::list_ports() {
+        Ok(ports) if idx < ports.len() => open_port(&ports[idx], requested, auto, state, identity).await,
+        Ok(ports) => cli_error(args.error_at(0, &format!("No port [{}]; 'ports' lists {} port(s)", idx, ports.len()))),
+        Err(e) => SystemEvent::Error {
+            source: "serial".to_string(),
+            message: e,
+        },
+    }
+}
+
+/// Open `port_info` at `requested` (default from config), or probe the configured rates with `auto`,
+/// then run the firmware handshake
+pub(super) async fn open_port(port_info: &PortInfo, requested: Option<u32>, auto: bool, state: &AppState, identity: &Identity) -> SystemEvent {
+    if let Err(e) = state.serial_queue.check(identity) {
+        return SystemEvent::Error { source: "serial".to_string(), message: e };
+    }
+    let serial_cfg = &state.config.serial;
+    let mut baud = if auto {
+        serial_cfg.auto_baud_rates.first().copied().unwrap_or(serial_cfg.default_baud_rate)
+    } else {
+        requested.unwrap_or(serial_cfg.default_baud_rate)
+    };
     
-    if let Some(idx) = index {
-        match SerialBridge::list_ports() {
-            Ok(ports) if idx < ports.len() => {
-                let port_info = &ports[idx];
-                
-                // On macOS prefer /dev/cu.* over /dev/tty.* and add a short retry loop for busy ports
-                let mut last_err: Option<String> = None;
-                let candidates: Vec<String> = if port_info.port_name.contains("/tty.") {
-                    vec![port_info.port_name.replace("/tty.", "/cu."), port_info.port_name.clone()]
-                } else {
-                    vec![port_info.port_name.clone()]
-                };
+    // On macOS prefer /dev/cu.* over /dev/tty.* and add a short retry loop for busy ports
+    let mut last_err: Option<String> = None;
+    let candidates: Vec<String> = if port_info.port_name.contains("/tty.") {
+        vec![port_info.port_name.replace("/tty.", "/cu."), port_info.port_name.clone()]
+    } else {
+        vec![port_info.port_name.clone()]
+    };
 
-                let mut connected = false;
-                for cand in &candidates {
-                    // up to 3 attempts in case the port is busy right after upload/reset
-                    for _attempt in 1..=3 {
-                        let result = {
-                            let mut serial = state.serial.write().await;
-                            serial.connect(cand, baud, port_info.board_name.clone())
-                        };
-                        match result {
-                            Ok(_) => { connected = true; break; }
-                            Err(e) => {
-                                last_err = Some(e.clone());
-                                let lower = e.to_lowercase();
-                                if lower.contains("busy") || lower.contains("device") || lower.contains("resource") {
-                                    // small backoff then retry
-                                    tokio::time::sleep(std::time::Duration::from_millis(700)).await;
-                                    continue;
-                                } else {
-                                    break; // non-busy error, stop retrying this candidate
-                                }
-                            }
-                        }
+    let mut connected = false;
+    for cand in &candidates {
+        // up to 3 attempts in case the port is busy right after upload/reset
+        for _attempt in 1..=3 {
+            let result = {
+                let mut serial = state.serial.write().await;
+                serial.connect(cand, baud, port_info.board_name.clone())
+            };
+            match result {
+                Ok(_) => { connected = true; break; }
+                Err(e) => {
+                    last_err = Some(e.clone());
+                    let lower = e.to_lowercase();
+                    if lower.contains("busy") || lower.contains("device") || lower.contains("resource") {
+                        // small backoff then retry
+                        tokio::time::sleep(std::time::Duration::from_millis(700)).await;
+                        continue;
+                    } else {
+                        break; // non-busy error, stop retrying this candidate
                     }
-                    if connected { break; }
                 }
+            }
+        }
+        if connected { break; }
+    }
 
-                if connected {
-                    state.broadcast(SystemEvent::SerialStatus {
-                        connected: true,
-                        port: Some(port_info.port_name.clone()),
-                        baud_rate: Some(baud),
-                        board_name: Some(port_info.board_name.clone()),
-                    });
-                    SystemEvent::Output {
-                        content: format!(
-                            "Connected: {} - {} @ {} baud",
-                            port_info.port_name, port_info.board_name, baud
-                        ),
-                    }
-                } else {
-                    let msg = if let Some(e) = last_err {
-                        let el = e.to_lowercase();
-                        if el.contains("busy") || el.contains("resource busy") || el.contains("device busy") {
-                            "Port is busy. Close Arduino IDE Serial Monitor/Plotter or any tool holding the port (screen, platformio, etc.). On macOS, try: lsof /dev/cu.* to see holders. After upload, re-open the web app and run 'ports' again; if needed, unplug/replug the USB to re-enumerate.".to_string()
-                        } else {
-                            e
-                        }
-                    } else {
-                        "Failed to open port (unknown error)".to_string()
-                    };
-                    SystemEvent::Error { source: "serial".to_string(), message: msg }
-                }
+    if connected && auto {
+        match detect_baud(state).await {
+            Some(rate) => baud = rate,
+            None => {
+                state.serial.write().await.disconnect();
+                let tried: Vec<String> = serial_cfg.auto_baud_rates.iter().map(|r| r.to_string()).collect();
+                return SystemEvent::Error {
+                    source: "serial".to_string(),
+                    message: format!(
+                        "No valid reply to '{}' at any of: {} baud. Check the sketch's Serial.begin() rate.",
+                        serial_cfg.probe_command,
+                        tried.join(", ")
+                    ),
+                };
             }
-            Ok(_) => SystemEvent::Error {
-                source: "serial".to_string(),
-                message: "Invalid port index".to_string(),
-            },
-            Err(e) => SystemEvent::Error {
-                source: "serial".to_string(),
-                message: e,
-            },
         }
-    } else {
-        SystemEvent::Error {
-            source: "serial".to_string(),
-            message: "Usage: connect <index> [baud]".to_string(),
+    }
+
+    if connected {
+        state.broadcast(SystemEvent::SerialStatus {
+            connected: true,
+            port: Some(port_info.port_name.clone()),
+            baud_rate: Some(baud),
+            board_name: Some(port_info.board_name.clone()),
+            auto_baud: auto,
+        });
+        let fw = serial_handshake(state, identity).await.unwrap_or_default();
+        let fw_note = if fw.is_known() {
+            state.broadcast(fw.to_event(&port_info.board_name));
+            format!("\nFirmware: {} ({} commands advertised)", fw.firmware_label(), fw.commands.len())
+        } else {
+            "\nFirmware: no handshake reply (commands will not be filtered)".to_string()
+        };
+        SystemEvent::Output {
+            content: format!(
+                "Connected: {} - {} @ {} baud{}{}",
+                port_info.port_name, port_info.board_name, baud,
+                if auto { " (auto-detected)" } else { "" },
+                fw_note
+            ),
         }
+    } else {
+        let msg = if let Some(e) = last_err {
+            let el = e.to_lowercase();
+            if el.contains("busy") || el.contains("resource busy") || el.contains("device busy") {
+                "Port is busy. Close Arduino IDE Serial Monitor/Plotter or any tool holding the port (screen, platformio, etc.). On macOS, try: lsof /dev/cu.* to see holders. After upload, re-open the web app and run 'ports' again; if needed, unplug/replug the USB to re-enumerate.".to_string()
+            } else {
+                e
+            }
+        } else {
+            "Failed to open port (unknown error)".to_string()
+        };
+        SystemEvent::Error { source: "serial".to_string(), message: msg }
     }
 }
 
-async fn handle_disconnect(state: &AppState) -> SystemEvent {
+/// Run blocking port I/O on a blocking thread with a clone of the bridge,
+/// so `state.serial` is not locked while waiting on the board
+async fn with_port<T: Send + 'static>(state: &AppState, f: impl FnOnce(SerialBridge) -> T + Send + 'static) -> Result<T, String> {
+    let serial = state.serial.read().await.clone();
+    tokio::task::spawn_blocking(move || f(serial))
+        .await
+        .map_err(|e| format!("Serial I/O task failed: {}", e))
+}
+
+/// Walk the configured baud list on the open port and keep the first rate
+/// at which the board answers the probe command with a readable line.
+async fn detect_baud(state: &AppState) -> Option<u32> {
+    let cfg = state.config.serial.clone();
+    let found = with_port(state, move |mut serial| {
+        for &rate in &cfg.auto_baud_rates {
+            if let Err(e) = serial.set_baud_rate(rate) {
+                log::warn!("Auto-baud: cannot switch to {}: {}", rate, e);
+                continue;
+            }
+            log::info!("Auto-baud: probing {} baud", rate);
+            if serial.probe(&cfg.probe_command, cfg.probe_timeout_ms) {
+                log::info!("Auto-baud: board answered at {} baud", rate);
+                return Some(rate);
+            }
+        }
+        None
+    })
+    .await
+    .ok()
+    .flatten()?;
+    // The shared port already runs at `found`; record it on the bridge in state too
+    state.serial.write().await.set_baud_rate(found).ok()?;
+    Some(found)
+}
+
+pub(super) async fn handle_disconnect(state: &AppState, identity: &Identity) -> SystemEvent {
+    if let Err(e) = state.serial_queue.check(identity) {
+        return SystemEvent::Error { source: "serial".to_string(), message: e };
+    }
     let mut serial = state.serial.write().await;
+    let board_id = serial.board_id();
     serial.disconnect();
+    state.boards.write().await.remove(&board_id);
     
     state.broadcast(SystemEvent::SerialStatus {
         connected: false,
         port: None,
         baud_rate: None,
         board_name: None,
+        auto_baud: false,
     });
     
     SystemEvent::Output {
@@ -486,96 +887,166 @@ async fn handle_status(state: &AppState) -> SystemEvent {
     } else {
         "Serial: Not connected".to_string()
     };
+    let mut msg = match state.serial_queue.holder() {
+        Some(h) => format!("{}\nLocked by {} (session {}) since {}", msg, h.user, h.session, h.since.format("%H:%M:%S")),
+        None => msg,
+    };
+    let waiting = state.serial_queue.waiting();
+    if waiting > 0 {
+        msg.push_str(&format!("\n{} command(s) waiting for the port", waiting));
+    }
     SystemEvent::Output { content: msg }
 }
 
-async fn handle_info(state: &AppState) -> SystemEvent {
-    // If current transport is MQTT, publish to per-component topic and return
-    if let Transport::Mqtt = *state.transport.read().await {
-        match publish_component_command(state, "info", "info").await {
-            Ok(_) => return SystemEvent::Output { content: "MQTT: info requested".into() },
-            Err(e) => return SystemEvent::Error { source: "mqtt".into(), message: e },
-        }
-    }
-    let serial = state.serial.read().await;
+/// `lock` / `unlock [force]`: hold the serial port for this session only
+const LOCK: Spec = Spec::new("lock", 0, Some(0));
+const UNLOCK: Spec = Spec::new("unlock [force] [--force]", 0, Some(1)).flags(&[Flag { long: "force", short: Some('f'), value: None }]);
 
-    if !serial.is_connected() {
-        return SystemEvent::Error {
-            source: "serial".to_string(),
-            message: "Not connected to board".to_string(),
-        };
+fn handle_lock(inv: &Invocation, state: &AppState, identity: &Identity) -> SystemEvent {
+    let args = match inv.args(if inv.name() == "lock" { &LOCK } else { &UNLOCK }) { Ok(a) => a, Err(e) => return cli_error(e) };
+    if args.get(0).is_some_and(|a| !a.eq_ignore_ascii_case("force")) {
+        return cli_error(args.error_at(0, "Expected 'force'"));
     }
-    // Forward to firmware: try 'INFO' first
-    if let Err(e) = serial.send_command("INFO") {
-        return SystemEvent::Error {
-            source: "serial".to_string(),
-            message: format!("Failed to send INFO: {}", e),
-        };
+    let result = if inv.name() == "lock" {
+        state.serial_queue.lock(identity).map(|h| format!("Serial port locked for {} (session {}); 'unlock' to release", h.user, h.session))
+    } else {
+        let force = args.flag("force") || args.get(0).is_some();
+        if force && identity.role < auth::Role::Admin {
+            Err("'unlock force' requires the admin role".to_string())
+        } else {
+            state.serial_queue.unlock(identity, force).map(|h| format!("Serial port unlocked (was held by {})", h.user))
+        }
+    };
+    match result {
+        Ok(content) => SystemEvent::Output { content },
+        Err(message) => SystemEvent::Error { source: "serial".to_string(), message },
     }
+}
 
-    // Read multiple lines for up to ~3s, accept SENSORS line wherever it appears
-    let mut sensors: Option<Vec<SensorDetail>> = None;
-    let mut attempts = 0;
-    let start = std::time::Instant::now();
-    while start.elapsed().as_millis() < 3000 {
-        match serial.read_line(400) {
-            Ok(line) => {
-                let line = line.trim();
-                if line.starts_with("SENSORS:") {
-                    let sensor_data = line.strip_prefix("SENSORS:").unwrap_or("");
-                    let s: Vec<SensorDetail> = sensor_data
-                        .split(',')
-                        .enumerate()
-                        .filter_map(|(i, part)| {
-                            let kv: Vec<&str> = part.split(':').collect();
-                            if kv.len() == 2 {
-                                Some(SensorDetail { id: (i+1) as u8, name: kv[0].trim().to_string(), pin: format!("Pin {}", kv[1].trim()) })
-                            } else { None }
-                        })
-                        .collect();
-                    sensors = Some(s);
-                    break;
-                }
-                // Ignore other banner lines
-            }
-            Err(_) => {
-                attempts += 1;
-                if attempts == 2 {
-                    let _ = serial.send_command("/INFO");
-                }
+async fn handle_info(state: &AppState, identity: &Identity) -> SystemEvent {
+    // If current transport is MQTT, publish to per-component topic and return
+    if let Transport::Mqtt = *state.transport.read().await {
+        // Let the info/state reply trigger a fresh VERSION/HELP round
+        {
+            let serial = state.serial.read().await;
+            let bid = serial.board_id();
+            drop(serial);
+            if let Some(info) = state.boards.write().await.get_mut(&bid) {
+                info.queried = false;
             }
         }
+        match publish_component_command(state, identity, "info", "info").await {
+            Ok(_) => return SystemEvent::Output { content: "MQTT: info requested".into() },
+            Err(e) => return e,
+        }
     }
 
-    if let Some(sensors) = sensors {
-        SystemEvent::SensorInfo {
-            sensors,
-            board: serial.get_board_name().unwrap_or("Unknown").to_string(),
-            firmware: "v1.0.1".to_string(),
+    let board_name = {
+        let serial = state.serial.read().await;
+        if !serial.is_connected() {
+            return SystemEvent::Error {
+                source: "serial".to_string(),
+                message: "Not connected to board".to_string(),
+            };
         }
+        serial.get_board_name().unwrap_or("Unknown").to_string()
+    };
+
+    let fw = match serial_handshake(state, identity).await {
+        Ok(fw) => fw,
+        Err(e) => return SystemEvent::Error { source: "serial".to_string(), message: e },
+    };
+    if fw.is_known() {
+        fw.to_event(&board_name)
     } else {
         SystemEvent::Output { content: "No sensor info returned from board (timeout). If sensors aren't connected, that's ok. Use 'status' or try commands like 'light on' or 'temp'.".to_string() }
     }
 }
 
-async fn forward_to_arduino(cmd: &str, state: &AppState) -> SystemEvent {
-    let serial = state.serial.read().await;
-    if !serial.is_connected() {
-        return SystemEvent::Error {
-            source: "serial".to_string(),
-            message: "Not connected".to_string(),
-        };
-    }
+/// Query VERSION / INFO / HELP over serial and remember the answers for the connected board
+async fn serial_handshake(state: &AppState, identity: &Identity) -> Result<FirmwareInfo, String> {
+    let _turn = state.serial_queue.begin(identity).await?;
+    let timeout_ms = state.config.serial.handshake_timeout_ms;
+    let info = with_port(state, move |serial| firmware::handshake(&serial, timeout_ms)).await?;
+    // Key the answers by the board's MQTT topic id so both transports share one entry:
+    // the ID: it reported, else a board already seen over MQTT under the same BOARD: name
+    let known = match (&info.id, &info.board) {
+        (Some(id), _) => Some(id.clone()),
+        (None, Some(name)) => state.boards.read().await.iter()
+            .find(|(_, b)| b.board.as_ref() == Some(name))
+            .map(|(id, _)| id.clone()),
+        (None, None) => None,
+    };
+    let board_id = {
+        let mut serial = state.serial.write().await;
+        if let Some(id) = known {
+            serial.set_board_id(id);
+        }
+        serial.board_id()
+    };
+    log::info!(
+        "Handshake {}: version={:?} firmware={:?} commands={}",
+        board_id, info.version, info.firmware, info.commands.len()
+    );
+    state.boards.write().await.insert(board_id, info.clone());
+    Ok(info)
+}
 
-    if let Err(e) = serial.send_command(cmd) {
-        return SystemEvent::Error {
-            source: "serial".to_string(),
-            message: format!("Send failed: {}", e),
-        };
+/// Reject device commands the board's firmware (default: the connected one) did not list in its CMDS: reply
+async fn ensure_supported(state: &AppState, board: Option<&str>, payload: &str) -> Result<(), SystemEvent> {
+    let board_id = match board {
+        Some(b) => b.to_string(),
+        None => state.serial.read().await.board_id(),
+    };
+    let boards = state.boards.read().await;
+    match boards.get(&board_id) {
+        Some(info) if !info.supports(payload) => Err(SystemEvent::Error {
+            source: "firmware".to_string(),
+            message: format!(
+                "'{}' is not supported by firmware {} on {} (supports: {})",
+                payload, info.firmware_label(), board_id, info.commands.join(", ")
+            ),
+        }),
+        _ => Ok(()),
     }
+}
+
+async fn forward_to_arduino(cmd: &str, state: &AppState, identity: &Identity) -> SystemEvent {
+    let _turn = match state.serial_queue.begin(identity).await {
+        Ok(turn) => turn,
+        Err(e) => return SystemEvent::Error { source: "serial".to_string(), message: e },
+    };
+    let board = {
+        let serial = state.serial.read().await;
+        if !serial.is_connected() {
+            return SystemEvent::Error {
+                source: "serial".to_string(),
+                message: "Not connected".to_string(),
+            };
+        }
+        serial.board_id()
+    };
+
+    let cmd = cmd.to_string();
+    let reply = with_port(state, move |serial| {
+        serial.send_command(&cmd).map_err(|e| format!("Send failed: {}", e))?;
+        serial.read_line(5000)
+    })
+    .await
+    .and_then(|r| r);
 
-    match serial.read_line(5000) {
-        Ok(response) => SystemEvent::Output { content: response },
+    match reply {
+        Ok(response) => {
+            if let Some(r) = readings::parse_reading(&response) {
+                state.broadcast(SystemEvent::Reading {
+                    board,
+                    sensor: r.sensor,
+                    value: r.value,
+                    unit: r.unit,
+                });
+            }
+            SystemEvent::Output { content: response }
+        }
         Err(e) => SystemEvent::Error { source: "serial".to_string(), message: e },
     }
 }
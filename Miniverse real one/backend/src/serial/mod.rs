@@ -1,5 +1,12 @@
 mod bridge;
 mod commands;
+mod queue;
+mod routes;
 
 pub use bridge::SerialBridge;
-pub use commands::handle_serial_command_with_transport;
+pub use commands::{check_mode, command_target, complete_command, handle_serial_command_with_transport};
+pub use queue::SerialQueue;
+pub use routes::{
+    connect_route, disconnect_route, led_route, publish_route, status_route, subscribe_route, subscriptions_route, temp_route,
+    unsubscribe_route,
+};
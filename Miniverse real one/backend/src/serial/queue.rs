@@ -0,0 +1,94 @@
+use chrono::{DateTime, Local};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::auth::Identity;
+
+/// Session holding the board exclusively via `lock`
+#[derive(Debug, Clone)]
+pub struct LockHolder {
+    pub session: u64,
+    pub user: String,
+    pub since: DateTime<Local>,
+}
+
+/// Serializes send/read transactions on the shared port so concurrent sessions
+/// can't swap each other's replies. Waiters are served in arrival order.
+#[derive(Debug, Default)]
+pub struct SerialQueue {
+    gate: Mutex<()>,
+    holder: std::sync::Mutex<Option<LockHolder>>,
+    waiting: AtomicUsize,
+}
+
+impl SerialQueue {
+    /// Fail if another session holds the exclusive lock
+    pub fn check(&self, identity: &Identity) -> Result<(), String> {
+        match self.holder() {
+            Some(h) if Some(h.session) != identity.session => Err(format!(
+                "Serial port is locked by {} (session {}) since {}",
+                h.user,
+                h.session,
+                h.since.format("%H:%M:%S")
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Wait for our turn on the port; the returned guard ends the transaction when dropped
+    pub async fn begin(&self, identity: &Identity) -> Result<MutexGuard<'_, ()>, String> {
+        self.check(identity)?;
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let guard = self.gate.lock().await;
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+        // The lock may have been taken while we were queued
+        self.check(identity)?;
+        log::debug!("Serial transaction for {} (session {:?})", identity.user, identity.session);
+        Ok(guard)
+    }
+
+    pub fn lock(&self, identity: &Identity) -> Result<LockHolder, String> {
+        let session = identity.session.ok_or("Only interactive sessions can lock the serial port")?;
+        let mut holder = self.holder.lock().map_err(|_| "Serial lock poisoned".to_string())?;
+        match holder.as_ref() {
+            Some(h) if h.session == session => Err("You already hold the serial lock".to_string()),
+            Some(h) => Err(format!("Serial port is locked by {} (session {})", h.user, h.session)),
+            None => {
+                let h = LockHolder { session, user: identity.user.clone(), since: Local::now() };
+                *holder = Some(h.clone());
+                Ok(h)
+            }
+        }
+    }
+
+    /// Release our own lock, or anyone's with `force`
+    pub fn unlock(&self, identity: &Identity, force: bool) -> Result<LockHolder, String> {
+        let mut holder = self.holder.lock().map_err(|_| "Serial lock poisoned".to_string())?;
+        match holder.as_ref() {
+            None => return Err("Serial port is not locked".to_string()),
+            Some(h) if !force && Some(h.session) != identity.session => {
+                return Err(format!("Serial lock is held by {} (session {})", h.user, h.session));
+            }
+            Some(_) => {}
+        }
+        holder.take().ok_or_else(|| "Serial port is not locked".to_string())
+    }
+
+    /// Drop the lock if this (closing) session holds it
+    pub fn release_session(&self, session: u64) -> Option<LockHolder> {
+        let mut holder = self.holder.lock().ok()?;
+        if holder.as_ref().is_some_and(|h| h.session == session) {
+            holder.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn holder(&self) -> Option<LockHolder> {
+        self.holder.lock().ok().and_then(|h| h.clone())
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+}
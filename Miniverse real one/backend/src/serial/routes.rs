@@ -0,0 +1,312 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::audit::AuditEntry;
+use crate::auth::{self, command_role, Access, Identity};
+use crate::events::SystemEvent;
+use crate::serial::commands::{self, light_payload};
+use crate::serial::SerialBridge;
+use crate::state::{AppState, Transport};
+
+// REST mirrors of the terminal commands. Callers have no session, so modes don't apply and
+// MQTT subscriptions land in the global set, which only admins may change. Each call needs the role its terminal command
+// needs and is audited like one.
+
+/// Map a command result to JSON: `{ "output" }`, or `{ "error", "source" }` with a fitting status
+fn respond(event: SystemEvent) -> HttpResponse {
+    match event {
+        SystemEvent::Output { content } => HttpResponse::Ok().json(serde_json::json!({ "output": content })),
+        SystemEvent::Error { source, message } => {
+            let mut status = match source.as_str() {
+                "cli" => HttpResponse::BadRequest(),
+                "acl" | "auth" => HttpResponse::Forbidden(),
+                "firmware" => HttpResponse::UnprocessableEntity(),
+                // Not connected, locked by a session, busy port
+                "serial" => HttpResponse::Conflict(),
+                _ => HttpResponse::BadGateway(),
+            };
+            status.json(serde_json::json!({ "error": message, "source": source }))
+        }
+        other => HttpResponse::Ok().json(other),
+    }
+}
+
+fn forbidden(message: String) -> HttpResponse {
+    HttpResponse::Forbidden().json(serde_json::json!({ "error": message }))
+}
+
+fn bad_request(message: String) -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({ "error": message }))
+}
+
+/// Same role as the equivalent terminal command line
+fn allowed(identity: &Identity, line: &str) -> Result<(), String> {
+    let name = line.split_whitespace().next().unwrap_or(line);
+    identity.allows(command_role(line), name)
+}
+
+fn parse_transport(name: Option<&str>) -> Result<Option<Transport>, String> {
+    match name.map(str::to_lowercase).as_deref() {
+        Some("serial") => Ok(Some(Transport::Serial)),
+        Some("mqtt") => Ok(Some(Transport::Mqtt)),
+        Some(other) => Err(format!("Unknown transport '{}'", other)),
+        None => Ok(None),
+    }
+}
+
+/// Record a REST call under the audit kind its WebSocket counterpart uses
+fn audit(state: &AppState, identity: &Identity, kind: &str, transport: Transport, target: String, payload: String, event: &SystemEvent) {
+    let (ok, result) = match event {
+        SystemEvent::Error { message, .. } => (false, message.clone()),
+        SystemEvent::Output { content } => (true, content.clone()),
+        other => (true, serde_json::to_string(other).unwrap_or_default()),
+    };
+    state.audit.record(AuditEntry {
+        id: 0,
+        at: chrono::Local::now(),
+        session: None,
+        user: identity.user.clone(),
+        transport: match transport { Transport::Serial => "serial", Transport::Mqtt => "mqtt" }.to_string(),
+        kind: kind.to_string(),
+        target,
+        payload,
+        ok,
+        result,
+    });
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectRequest {
+    // Index from GET /api/ports
+    port: usize,
+    baud: Option<u32>,
+    // Probe the configured baud rates instead
+    #[serde(default)]
+    auto: bool,
+}
+
+/// POST /api/serial/connect {port, baud?, auto?}
+pub async fn connect_route(body: web::Json<ConnectRequest>, identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    let state = state.get_ref();
+    let req = body.into_inner();
+    let line = match (req.auto, req.baud) {
+        (true, _) => format!("connect {} auto", req.port),
+        (false, Some(baud)) => format!("connect {} {}", req.port, baud),
+        (false, None) => format!("connect {}", req.port),
+    };
+    if let Err(e) = allowed(&identity, &line) {
+        return forbidden(e);
+    }
+    let ports = match SerialBridge::list_ports() {
+        Ok(ports) => ports,
+        Err(message) => return respond(SystemEvent::Error { source: "serial".to_string(), message }),
+    };
+    let Some(port) = ports.get(req.port) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No port [{}]; GET /api/ports lists {} port(s)", req.port, ports.len())
+        }));
+    };
+    let event = commands::open_port(port, req.baud, req.auto, state, &identity).await;
+    audit(state, &identity, "command", Transport::Serial, port.port_name.clone(), line, &event);
+    respond(event)
+}
+
+/// DELETE /api/serial
+pub async fn disconnect_route(identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    let state = state.get_ref();
+    if let Err(e) = allowed(&identity, "disconnect") {
+        return forbidden(e);
+    }
+    let target = state.serial.read().await.get_port_name().unwrap_or_default().to_string();
+    let event = commands::handle_disconnect(state, &identity).await;
+    audit(state, &identity, "command", Transport::Serial, target, "disconnect".to_string(), &event);
+    respond(event)
+}
+
+/// GET /api/serial/status
+pub async fn status_route(identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    let state = state.get_ref();
+    if let Err(e) = allowed(&identity, "status") {
+        return forbidden(e);
+    }
+    let transport = match *state.transport.read().await { Transport::Serial => "serial", Transport::Mqtt => "mqtt" };
+    let serial = state.serial.read().await;
+    let connected = serial.is_connected();
+    let lock = state.serial_queue.holder().map(|h| serde_json::json!({ "user": h.user, "session": h.session, "since": h.since }));
+    HttpResponse::Ok().json(serde_json::json!({
+        "connected": connected,
+        "port": serial.get_port_name(),
+        "baud_rate": connected.then(|| serial.get_baud_rate()),
+        "board_name": serial.get_board_name(),
+        "transport": transport,
+        "lock": lock,
+        "waiting": state.serial_queue.waiting(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LedRequest {
+    // Full on or off, like `light on|off`
+    on: Option<bool>,
+    // 0-255, like `set light`; wins over `on`
+    brightness: Option<u8>,
+    color: Option<String>,
+    transport: Option<String>,
+}
+
+/// POST /api/devices/{id}/led {on? | brightness?, color?, transport?}
+pub async fn led_route(
+    path: web::Path<String>,
+    body: web::Json<LedRequest>,
+    identity: web::ReqData<Identity>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let state = state.get_ref();
+    let board = path.into_inner();
+    let req = body.into_inner();
+    let transport = match parse_transport(req.transport.as_deref()) {
+        Ok(t) => t,
+        Err(e) => return bad_request(e),
+    };
+    let value = match (req.brightness, req.on) {
+        (Some(v), _) => v,
+        (None, Some(on)) => if on { 255 } else { 0 },
+        (None, None) => return bad_request("Provide 'on' or 'brightness'".to_string()),
+    };
+    let payload = light_payload(value, req.color.as_deref());
+    if let Err(e) = allowed(&identity, &payload) {
+        return forbidden(e);
+    }
+    let effective = transport.unwrap_or(*state.transport.read().await);
+    let event = commands::send_device(state, transport, &identity, Some(&board), "led", &payload).await;
+    audit(state, &identity, "command", effective, device_target(state, effective, &board, "led").await, payload, &event);
+    respond(event)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransportQuery {
+    transport: Option<String>,
+}
+
+// Serial replies come back on the request; MQTT ones arrive as board state a moment later
+const READING_TIMEOUT_MS: u64 = 5000;
+
+/// GET /api/devices/{id}/temp?transport= waits for the board's reading
+pub async fn temp_route(
+    path: web::Path<String>,
+    query: web::Query<TransportQuery>,
+    identity: web::ReqData<Identity>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let state = state.get_ref();
+    let board = path.into_inner();
+    let transport = match parse_transport(query.transport.as_deref()) {
+        Ok(t) => t,
+        Err(e) => return bad_request(e),
+    };
+    if let Err(e) = allowed(&identity, "temp") {
+        return forbidden(e);
+    }
+    let effective = transport.unwrap_or(*state.transport.read().await);
+    // Listen before sending so the reading can't slip past
+    let mut events = state.subscribe();
+    let event = commands::send_device(state, transport, &identity, Some(&board), "temp", "temp").await;
+    audit(state, &identity, "command", effective, device_target(state, effective, &board, "temp").await, "temp".to_string(), &event);
+    let SystemEvent::Output { content } = event else { return respond(event) };
+
+    let wait = async {
+        loop {
+            match events.recv().await {
+                Ok(SystemEvent::Reading { board: b, sensor, value, unit }) if b == board && sensor == "temp" => return Some((value, unit)),
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => return None,
+            }
+        }
+    };
+    match tokio::time::timeout(std::time::Duration::from_millis(READING_TIMEOUT_MS), wait).await {
+        Ok(Some((value, unit))) => HttpResponse::Ok().json(serde_json::json!({ "board": board, "value": value, "unit": unit, "output": content })),
+        _ => HttpResponse::GatewayTimeout().json(serde_json::json!({
+            "error": format!("No temperature reading from {} within {} ms", board, READING_TIMEOUT_MS),
+            "output": content,
+        })),
+    }
+}
+
+// Where a device command for `board` went, for the audit log
+async fn device_target(state: &AppState, transport: Transport, board: &str, component: &str) -> String {
+    match transport {
+        Transport::Mqtt => format!("miniverse/{}/{}/command", board, component),
+        Transport::Serial => state.serial.read().await.get_port_name().unwrap_or("(no port)").to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishRequest {
+    topic: String,
+    #[serde(default)]
+    payload: String,
+}
+
+/// POST /api/mqtt/publish {topic, payload}
+pub async fn publish_route(body: web::Json<PublishRequest>, identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    let state = state.get_ref();
+    let req = body.into_inner();
+    if let Err(e) = allowed(&identity, "mqtt pub") {
+        return forbidden(e);
+    }
+    if let Err(message) = auth::check_topic(&identity, state, Access::Publish, &req.topic) {
+        return forbidden(message);
+    }
+    let event = commands::mqtt_publish(&req.topic, &req.payload, state).await;
+    audit(state, &identity, "publish", Transport::Mqtt, req.topic, req.payload, &event);
+    respond(event)
+}
+
+/// GET /api/mqtt/subscriptions: the global filters and those held by sessions
+pub async fn subscriptions_route(identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    let state = state.get_ref();
+    if let Err(e) = allowed(&identity, "mqtt subs") {
+        return forbidden(e);
+    }
+    let Ok(subs) = state.mqtt_subs.read() else {
+        return HttpResponse::InternalServerError().json(serde_json::json!({ "error": "Subscription table unavailable" }));
+    };
+    let sessions: Vec<serde_json::Value> = subs
+        .session_filters()
+        .into_iter()
+        .map(|(topic, sessions)| serde_json::json!({ "topic": topic, "sessions": sessions }))
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({ "global": subs.global(), "sessions": sessions }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopicRequest {
+    topic: String,
+}
+
+/// POST /api/mqtt/subscriptions {topic}: adds a global filter, so admin only
+pub async fn subscribe_route(body: web::Json<TopicRequest>, identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    change_subscription(true, body.into_inner().topic, &identity, state.get_ref()).await
+}
+
+/// DELETE /api/mqtt/subscriptions?topic=
+pub async fn unsubscribe_route(query: web::Query<TopicRequest>, identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    change_subscription(false, query.into_inner().topic, &identity, state.get_ref()).await
+}
+
+async fn change_subscription(add: bool, topic: String, identity: &Identity, state: &AppState) -> HttpResponse {
+    if let Err(e) = allowed(identity, if add { "mqtt sub" } else { "mqtt unsub" }) {
+        return forbidden(e);
+    }
+    if let Err(message) = auth::check_topic(identity, state, Access::Subscribe, &topic) {
+        return forbidden(message);
+    }
+    let event = if add {
+        commands::mqtt_subscribe(&topic, state, identity).await
+    } else {
+        commands::mqtt_unsubscribe(&topic, state, identity).await
+    };
+    let action = if add { "subscribe" } else { "unsubscribe" };
+    audit(state, identity, "subscribe", Transport::Mqtt, topic, action.to_string(), &event);
+    respond(event)
+}
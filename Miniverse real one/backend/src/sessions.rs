@@ -0,0 +1,114 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Identity;
+use crate::events::SystemEvent;
+use crate::state::AppState;
+
+/// What `who` and `GET /api/sessions` show for one WebSocket connection
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub session: u64,
+    pub user: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    pub transport: String,
+    pub mode: SessionMode,
+    // Boards this session filters on; empty means all
+    pub boards: Vec<String>,
+    pub connected_at: DateTime<Local>,
+}
+
+impl SessionInfo {
+    pub fn display_name(&self) -> String {
+        match &self.nickname {
+            Some(nick) => format!("{} ({})", nick, self.user),
+            None => self.user.clone(),
+        }
+    }
+}
+
+/// Terminal mode of a session: device commands in Normal, port and transport setup in Config.
+/// Sessions start in Normal; `config` and `exit` move between the two.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionMode {
+    #[default]
+    Normal,
+    Config,
+}
+
+impl SessionMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            SessionMode::Normal => "normal",
+            SessionMode::Config => "config",
+        }
+    }
+
+    /// The mode `requested` leads to from here
+    pub fn switch(self, requested: &str) -> Result<SessionMode, String> {
+        let target = match requested.to_lowercase().as_str() {
+            "config" => SessionMode::Config,
+            "normal" | "exit" | "end" => SessionMode::Normal,
+            other => return Err(format!("Unknown mode '{}' (config or normal)", other)),
+        };
+        if target == self {
+            return Err(format!("Already in {} mode", target.name().to_uppercase()));
+        }
+        Ok(target)
+    }
+}
+
+/// Mode of the session behind `identity`; None for the backend's own callers
+pub fn mode_of(state: &AppState, identity: &Identity) -> Option<SessionMode> {
+    let id = identity.session?;
+    state.sessions.read().ok()?.get(&id).map(|s| s.mode)
+}
+
+/// Move the session behind `identity` to the `requested` mode
+pub fn set_mode(state: &AppState, identity: &Identity, requested: &str) -> Result<SessionMode, String> {
+    let id = identity.session.ok_or("Modes belong to terminal sessions")?;
+    let mut sessions = state.sessions.write().map_err(|_| "Session table poisoned".to_string())?;
+    let session = sessions.get_mut(&id).ok_or("Session is gone")?;
+    session.mode = session.mode.switch(requested)?;
+    Ok(session.mode)
+}
+
+/// 1-24 letters, digits, `-`, `_` or `.`
+pub fn valid_nickname(nick: &str) -> bool {
+    (1..=24).contains(&nick.len()) && nick.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+pub fn list(state: &AppState) -> Vec<SessionInfo> {
+    state.sessions.read().map(|s| s.values().cloned().collect()).unwrap_or_default()
+}
+
+/// `who`
+pub fn handle_who(state: &AppState, identity: &Identity) -> SystemEvent {
+    let sessions = list(state);
+    if sessions.is_empty() {
+        return SystemEvent::Output { content: "No active sessions".to_string() };
+    }
+    let mut out = format!("Sessions ({}):\n", sessions.len());
+    for s in &sessions {
+        let boards = if s.boards.is_empty() { "all boards".to_string() } else { s.boards.join(",") };
+        out.push_str(&format!(
+            "  #{:<3} {:<24} {:<6} {:<6} {:<12} since {}{}\n",
+            s.session,
+            s.display_name(),
+            s.transport,
+            s.mode.name(),
+            boards,
+            s.connected_at.format("%H:%M:%S"),
+            if identity.session == Some(s.session) { "  <- you" } else { "" }
+        ));
+    }
+    SystemEvent::Output { content: out }
+}
+
+/// GET /api/sessions
+pub async fn sessions_route(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(list(&state))
+}
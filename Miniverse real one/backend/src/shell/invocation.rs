@@ -0,0 +1,272 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::shell::lexer::{point_at, tokenize, Token};
+
+/// An option a command accepts: `--long`, `-s`, and a value name if it takes one
+#[derive(Debug, Clone, Copy)]
+pub struct Flag {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: Option<&'static str>,
+}
+
+/// The shape of a command's arguments
+#[derive(Debug, Clone, Copy)]
+pub struct Spec {
+    pub usage: &'static str,
+    pub min: usize,
+    // None: any number (the tail is usually read with `rest`)
+    pub max: Option<usize>,
+    pub flags: &'static [Flag],
+    // Options only before the first positional, so a nested command line keeps its own
+    pub options_first: bool,
+}
+
+impl Spec {
+    pub const fn new(usage: &'static str, min: usize, max: Option<usize>) -> Self {
+        Self { usage, min, max, flags: &[], options_first: false }
+    }
+
+    pub const fn flags(mut self, flags: &'static [Flag]) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub const fn options_first(mut self) -> Self {
+        self.options_first = true;
+        self
+    }
+}
+
+/// A tokenized command line: the command word and everything after it.
+/// `subcommand()` peels off one more word, so `mqtt pub ...` is handled the same way
+/// and errors still point into the full line.
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    line: String,
+    name: Token,
+    // Lowercased command word
+    lower: String,
+    tokens: Vec<Token>,
+}
+
+impl Invocation {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut tokens = tokenize(line)?;
+        if tokens.is_empty() {
+            return Err("Empty command".to_string());
+        }
+        let name = tokens.remove(0);
+        let lower = name.text.to_lowercase();
+        Ok(Self { line: line.to_string(), name, lower, tokens })
+    }
+
+    /// Lowercased command word
+    pub fn name(&self) -> &str {
+        &self.lower
+    }
+
+    /// The next word as its own invocation, or None when nothing follows
+    pub fn subcommand(&self) -> Option<Invocation> {
+        let (first, rest) = self.tokens.split_first()?;
+        Some(Invocation {
+            line: self.line.clone(),
+            lower: first.text.to_lowercase(),
+            name: first.clone(),
+            tokens: rest.to_vec(),
+        })
+    }
+
+    /// Everything after the command word as typed, e.g. the command line a `watch` wraps
+    pub fn tail(&self) -> &str {
+        self.tokens.first().map_or("", |t| self.line[t.start..].trim_end())
+    }
+
+    /// Raw words after the command, for handlers that only peek
+    pub fn words(&self) -> Vec<&str> {
+        self.tokens.iter().map(|t| t.text.as_str()).collect()
+    }
+
+    /// Sort the words into positionals and options according to `spec`
+    pub fn args(&self, spec: &Spec) -> Result<Args<'_>, String> {
+        let mut positional = Vec::new();
+        let mut flags: Vec<(&'static str, Option<Token>)> = Vec::new();
+        let mut options_done = false;
+        let mut iter = self.tokens.iter().peekable();
+        while let Some(tok) = iter.next() {
+            let looks_like_option = !tok.quoted
+                && !options_done
+                && tok.text.len() > 1
+                && tok.text.starts_with('-')
+                && !tok.text[1..].starts_with(|c: char| c.is_ascii_digit() || c == '.');
+            if !looks_like_option {
+                positional.push(tok);
+                if spec.options_first {
+                    options_done = true;
+                }
+                continue;
+            }
+            if tok.text == "--" {
+                options_done = true;
+                continue;
+            }
+            let (flag, inline) = match tok.text.strip_prefix("--") {
+                Some(long) => {
+                    let (name, value) = match long.split_once('=') {
+                        Some((n, v)) => (n, Some(v)),
+                        None => (long, None),
+                    };
+                    (spec.flags.iter().find(|f| f.long == name), value)
+                }
+                None if tok.text.chars().count() == 2 => {
+                    let c = tok.text.chars().nth(1);
+                    (spec.flags.iter().find(|f| f.short.is_some() && f.short == c), None)
+                }
+                None => (None, None),
+            };
+            let Some(flag) = flag else {
+                return Err(self.error_at(tok, &format!("Unknown option '{}'", tok.text), spec));
+            };
+            let value = match (flag.value, inline) {
+                (None, None) => None,
+                (None, Some(_)) => return Err(self.error_at(tok, &format!("--{} takes no value", flag.long), spec)),
+                (Some(_), Some(v)) => {
+                    // Point at the value part of `--name=value`
+                    let at = tok.start + tok.text.find('=').map_or(0, |i| i + 1);
+                    Some(Token { text: v.to_string(), start: at, end: tok.end, quoted: tok.quoted })
+                }
+                (Some(what), None) => match iter.next() {
+                    Some(v) => Some(v.clone()),
+                    None => return Err(self.error_after(&format!("--{} needs a {}", flag.long, what), spec)),
+                },
+            };
+            flags.push((flag.long, value));
+        }
+
+        if positional.len() < spec.min {
+            let what = spec.usage.split_whitespace().filter(|w| w.starts_with('<')).nth(positional.len()).unwrap_or("argument");
+            return Err(self.error_after(&format!("Missing {}", what), spec));
+        }
+        if let Some(extra) = spec.max.and_then(|max| positional.get(max)) {
+            return Err(self.error_at(extra, &format!("Unexpected argument '{}'", extra.text), spec));
+        }
+        Ok(Args { inv: self, spec: *spec, positional, flags })
+    }
+
+    /// Error pointing at the command word itself
+    pub fn error_at_name(&self, message: &str) -> String {
+        point_at(&self.line, self.name.start, self.name.end, message)
+    }
+
+    fn error_at(&self, tok: &Token, message: &str, spec: &Spec) -> String {
+        format!("{}\nUsage: {}", point_at(&self.line, tok.start, tok.end, message), spec.usage)
+    }
+
+    fn error_after(&self, message: &str, spec: &Spec) -> String {
+        let end = self.line.trim_end().len();
+        format!("{}\nUsage: {}", point_at(&self.line, end, end + 1, message), spec.usage)
+    }
+}
+
+/// Typed view of one invocation's arguments
+#[derive(Debug)]
+pub struct Args<'a> {
+    inv: &'a Invocation,
+    spec: Spec,
+    positional: Vec<&'a Token>,
+    // Values are owned: `--name=value` has no token of its own
+    flags: Vec<(&'static str, Option<Token>)>,
+}
+
+impl<'a> Args<'a> {
+    pub fn len(&self) -> usize {
+        self.positional.len()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&'a str> {
+        self.positional.get(i).map(|t| t.text.as_str())
+    }
+
+    /// Positional `i` converted to `T`, pointing at the word if it doesn't parse
+    pub fn parse<T: FromStr>(&self, i: usize, what: &str) -> Result<Option<T>, String>
+    where
+        T::Err: Display,
+    {
+        match self.positional.get(i) {
+            Some(tok) => tok.text.parse::<T>().map(Some).map_err(|e| self.error_at(i, &format!("Invalid {} '{}': {}", what, tok.text, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `parse`, but the word must be there
+    pub fn require<T: FromStr>(&self, i: usize, what: &str) -> Result<T, String>
+    where
+        T::Err: Display,
+    {
+        self.parse(i, what)?.ok_or_else(|| self.error_at(i, &format!("Missing {}", what)))
+    }
+
+    pub fn flag(&self, long: &str) -> bool {
+        self.flags.iter().any(|(name, _)| *name == long)
+    }
+
+    /// The value of `--long` converted to `T`
+    pub fn parse_value<T: FromStr>(&self, long: &str, what: &str) -> Result<Option<T>, String>
+    where
+        T::Err: Display,
+    {
+        let Some((_, Some(tok))) = self.flags.iter().rev().find(|(name, _)| *name == long) else { return Ok(None) };
+        tok.text
+            .parse::<T>()
+            .map(Some)
+            .map_err(|e| self.inv.error_at(tok, &format!("Invalid {} '{}': {}", what, tok.text, e), &self.spec))
+    }
+
+    /// Everything from positional `i` on, as typed: spacing, quotes and escapes survive,
+    /// whether it is one word or several (`mqtt pub t "a  b"`, `run lcd show 'x'; temp`)
+    pub fn rest(&self, i: usize) -> String {
+        match self.positional.get(i) {
+            Some(first) => self.inv.line[first.start..].trim_end().to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Error pointing at positional `i` (or the end of the line if it's missing)
+    pub fn error_at(&self, i: usize, message: &str) -> String {
+        match self.positional.get(i) {
+            Some(tok) => self.inv.error_at(tok, message, &self.spec),
+            None => self.inv.error_after(message, &self.spec),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUB: Spec = Spec::new("pub <topic> [payload...]", 1, None);
+
+    fn rest(line: &str) -> String {
+        let inv = Invocation::parse(line).unwrap();
+        let args = inv.args(&PUB).unwrap();
+        args.rest(1)
+    }
+
+    #[test]
+    fn rest_is_raw_for_one_word_or_several() {
+        assert_eq!(rest(r#"pub t "a b""#), r#""a b""#);
+        assert_eq!(rest(r#"pub t "a b" c"#), r#""a b" c"#);
+        assert_eq!(rest(r#"pub t {"on":true}"#), r#"{"on":true}"#);
+        assert_eq!(rest("pub t a   b  "), "a   b");
+        assert_eq!(rest("pub t"), "");
+    }
+
+    #[test]
+    fn words_are_unquoted() {
+        let inv = Invocation::parse(r#"lcd show "a b" 'c'"#).unwrap();
+        assert_eq!(inv.name(), "lcd");
+        assert_eq!(inv.words(), ["show", "a b", "c"]);
+        assert_eq!(inv.tail(), r#"show "a b" 'c'"#);
+    }
+}
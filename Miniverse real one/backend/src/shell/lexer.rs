@@ -0,0 +1,171 @@
+/// One word of a command line after quote and escape processing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    // Byte range in the original line, quotes included
+    pub start: usize,
+    pub end: usize,
+    // Any part was quoted or escaped, so it can't be an option
+    pub quoted: bool,
+}
+
+/// Shell-style split: whitespace separates words, `'...'` is literal, `"..."` allows
+/// `\"`, `\\`, `\n` and `\t`, and a backslash outside quotes escapes the next character.
+/// Adjacent pieces join into one word (`led="a b"` is one token).
+pub fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, ch)) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut text = String::new();
+        let mut quoted = false;
+        let mut end = start;
+        while let Some(&(at, ch)) = chars.peek() {
+            if ch.is_whitespace() {
+                break;
+            }
+            chars.next();
+            end = at + ch.len_utf8();
+            match ch {
+                '\'' => {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some((i, '\'')) => { end = i + 1; break; }
+                            Some((_, c)) => text.push(c),
+                            None => return Err(point_at(line, at, line.len(), "Missing closing single quote")),
+                        }
+                    }
+                }
+                '"' => {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some((i, '"')) => { end = i + 1; break; }
+                            Some((i, '\\')) => match chars.next() {
+                                Some((_, 'n')) => text.push('\n'),
+                                Some((_, 't')) => text.push('\t'),
+                                Some((_, c @ ('"' | '\\'))) => text.push(c),
+                                Some((_, c)) => { text.push('\\'); text.push(c); }
+                                None => return Err(point_at(line, i, line.len(), "Dangling backslash")),
+                            },
+                            Some((_, c)) => text.push(c),
+                            None => return Err(point_at(line, at, line.len(), "Missing closing double quote")),
+                        }
+                    }
+                }
+                '\\' => {
+                    quoted = true;
+                    match chars.next() {
+                        Some((i, c)) => { text.push(c); end = i + c.len_utf8(); }
+                        None => return Err(point_at(line, at, line.len(), "Dangling backslash")),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        tokens.push(Token { text, start, end, quoted });
+    }
+    Ok(tokens)
+}
+
+/// The quoting rules of `tokenize`, fed one character at a time, for splitting
+/// text on separators before it is tokenized
+#[derive(Debug, Default)]
+pub struct Quoting {
+    quote: Option<char>,
+    escaped: bool,
+}
+
+impl Quoting {
+    /// Take the next character; true when it is outside quotes, unescaped and not itself a quote or backslash
+    pub fn bare(&mut self, ch: char) -> bool {
+        if self.escaped {
+            self.escaped = false;
+            return false;
+        }
+        match (self.quote, ch) {
+            (Some('\''), '\'') | (Some('"'), '"') => self.quote = None,
+            (Some('"') | None, '\\') => self.escaped = true,
+            (None, '\'' | '"') => self.quote = Some(ch),
+            (None, _) => return true,
+            _ => {}
+        }
+        false
+    }
+}
+
+/// Split on `sep` outside quotes, keeping quotes and escapes for the pieces' own tokenizing
+pub fn split_unquoted(s: &str, sep: char) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut quoting = Quoting::default();
+    for ch in s.chars() {
+        if quoting.bare(ch) && ch == sep {
+            out.push(std::mem::take(&mut cur));
+            continue;
+        }
+        cur.push(ch);
+    }
+    out.push(cur);
+    out.into_iter().map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect()
+}
+
+/// `message`, then the line with a caret under bytes `start..end`
+pub fn point_at(line: &str, start: usize, end: usize, message: &str) -> String {
+    let start = start.min(line.len());
+    let end = end.clamp(start, line.len());
+    let pad = line[..start].chars().count();
+    let width = line[start..end].chars().count().max(1);
+    format!("{}\n  {}\n  {}{}", message, line, " ".repeat(pad), "^".repeat(width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        tokenize(line).unwrap().into_iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn splits_on_whitespace_and_joins_quoted_pieces() {
+        assert_eq!(words("  lcd   show  hi "), vec!["lcd", "show", "hi"]);
+        assert_eq!(words(r#"lcd show "a b" 'c d'"#), vec!["lcd", "show", "a b", "c d"]);
+        assert_eq!(words(r#"led="a b"c"#), vec!["led=a bc"]);
+        assert!(words("").is_empty());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(words(r#""say \"hi\"\n" 'no \n here'"#), vec!["say \"hi\"\n", r"no \n here"]);
+        assert_eq!(words(r"a\ b \-x"), vec!["a b", "-x"]);
+        assert_eq!(words(r#""keep \q""#), vec![r"keep \q"]);
+    }
+
+    #[test]
+    fn tracks_spans_and_quoting() {
+        let tokens = tokenize(r#"mqtt pub "x y""#).unwrap();
+        assert_eq!((tokens[2].start, tokens[2].end), (9, 14));
+        assert!(tokens[2].quoted);
+        assert!(!tokens[0].quoted);
+    }
+
+    #[test]
+    fn unterminated_input_points_at_the_problem() {
+        let err = tokenize(r#"lcd show "abc"#).unwrap_err();
+        assert!(err.starts_with("Missing closing double quote"));
+        assert!(err.ends_with("           ^^^^"));
+        assert!(tokenize("echo 'x").unwrap_err().starts_with("Missing closing single quote"));
+        assert!(tokenize(r"echo \").unwrap_err().starts_with("Dangling backslash"));
+    }
+
+    #[test]
+    fn splits_on_separators_outside_quotes() {
+        assert_eq!(split_unquoted(r#"lcd show "a;b"; temp ;; "#, ';'), vec![r#"lcd show "a;b""#, "temp"]);
+        assert_eq!(split_unquoted(r"a\;b;c", ';'), vec![r"a\;b", "c"]);
+    }
+}
@@ -0,0 +1,6 @@
+mod invocation;
+mod lexer;
+pub mod registry;
+
+pub use invocation::{Flag, Invocation, Spec};
+pub use lexer::{split_unquoted, tokenize, Quoting};
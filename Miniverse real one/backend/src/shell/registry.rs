@@ -0,0 +1,253 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::auth::Identity;
+use crate::events::SystemEvent;
+use crate::serial::SerialBridge;
+use crate::sessions::SessionMode;
+use crate::shell::{tokenize, Invocation};
+use crate::state::{AppState, Transport};
+
+pub type HandlerFuture<'a> = Pin<Box<dyn Future<Output = SystemEvent> + Send + 'a>>;
+
+/// Everything a handler may need besides its own arguments
+#[derive(Clone, Copy)]
+pub struct Ctx<'a> {
+    pub state: &'a AppState,
+    // The session's transport, if it has one; otherwise the global setting applies
+    pub transport: Option<Transport>,
+    pub identity: &'a Identity,
+}
+
+pub type Handler = for<'a> fn(&'a Invocation, Ctx<'a>) -> HandlerFuture<'a>;
+
+/// Wrap a synchronous handler's result
+pub fn ready<'a>(event: SystemEvent) -> HandlerFuture<'a> {
+    Box::pin(std::future::ready(event))
+}
+
+/// Terminal mode a command belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Any,
+    Normal,
+    Config,
+}
+
+impl Mode {
+    fn allows(self, current: SessionMode) -> bool {
+        match self {
+            Mode::Any => true,
+            Mode::Normal => current == SessionMode::Normal,
+            Mode::Config => current == SessionMode::Config,
+        }
+    }
+}
+
+/// Which transport a command talks over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Via {
+    // Handled by the backend itself
+    Backend,
+    // Sent to the board over the session's transport (serial or MQTT)
+    Board,
+    Serial,
+    Mqtt,
+}
+
+/// What a positional argument is, for completion
+#[derive(Debug, Clone, Copy)]
+pub enum Arg {
+    Words(&'static [&'static str]),
+    // Depends on the subcommand in the first position
+    BySub(&'static [(&'static str, Arg)]),
+    Port,
+    Baud,
+    // Known topics: global, this session's and each board's
+    Topic,
+    // Topics this session subscribed to
+    OwnTopic,
+    // Another command's name (`help <command>`)
+    Name,
+    // A whole nested command line (`watch 2s <command>`, `run <command>`)
+    Command,
+    Free,
+}
+
+/// One terminal command
+pub struct CommandDef {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub group: &'static str,
+    // First line is the synopsis shown in `help`
+    pub usage: &'static [&'static str],
+    pub summary: &'static str,
+    pub mode: Mode,
+    pub via: Via,
+    // Empty: the command takes no arguments
+    pub args: &'static [Arg],
+    pub handler: Handler,
+}
+
+impl CommandDef {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    // What to complete at positional `pos`, given the words before it
+    fn slot(&self, pos: usize, before: &[String]) -> Option<Arg> {
+        let arg = match self.args.get(pos) {
+            Some(arg) => *arg,
+            None => match self.args.last() {
+                Some(Arg::Command) => Arg::Command,
+                _ => return None,
+            },
+        };
+        match arg {
+            Arg::BySub(table) => {
+                let sub = before.first()?.to_lowercase();
+                table.iter().find(|(name, _)| *name == sub).map(|(_, arg)| *arg)
+            }
+            other => Some(other),
+        }
+    }
+
+    fn describe_mode(&self) -> &'static str {
+        match self.mode {
+            Mode::Any => "any",
+            Mode::Normal => "normal",
+            Mode::Config => "config",
+        }
+    }
+
+    fn describe_via(&self) -> &'static str {
+        match self.via {
+            Via::Backend => "backend",
+            Via::Board => "board (serial or MQTT, per 'transport')",
+            Via::Serial => "serial",
+            Via::Mqtt => "MQTT",
+        }
+    }
+}
+
+pub fn find<'a>(commands: &'a [CommandDef], name: &str) -> Option<&'a CommandDef> {
+    commands.iter().find(|c| c.matches(name))
+}
+
+/// Can `def` run in the session's `current` mode? Callers without a session (scheduler, rules) always can.
+pub fn check_mode(def: &CommandDef, current: Option<SessionMode>) -> Result<(), String> {
+    match current {
+        Some(mode) if !def.mode.allows(mode) => Err(match def.mode {
+            Mode::Config => format!("'{}' is a CONFIG mode command; type 'config' first", def.name),
+            _ => format!("'{}' is a NORMAL mode command; type 'exit' to leave CONFIG mode", def.name),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// `help`: every command by group
+pub fn help_text(commands: &[CommandDef]) -> String {
+    let mut out = String::from("Commands ('help <command>' for details):\n");
+    let mut groups: Vec<&str> = Vec::new();
+    for c in commands {
+        if !groups.contains(&c.group) {
+            groups.push(c.group);
+        }
+    }
+    for group in groups {
+        out.push_str(&format!("\n{}\n", group));
+        for c in commands.iter().filter(|c| c.group == group) {
+            let mode = if c.mode == Mode::Config { " [config]" } else { "" };
+            for (i, line) in c.usage.iter().enumerate() {
+                match i {
+                    0 => out.push_str(&format!("  {:<40} {}{}\n", line, c.summary, mode)),
+                    _ => out.push_str(&format!("  {}\n", line)),
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `help <command>`
+pub fn command_help(c: &CommandDef) -> String {
+    let mut out = format!("{} - {}\nUsage:\n", c.name, c.summary);
+    for line in c.usage {
+        out.push_str(&format!("  {}\n", line));
+    }
+    if !c.aliases.is_empty() {
+        out.push_str(&format!("Aliases: {}\n", c.aliases.join(", ")));
+    }
+    out.push_str(&format!("Mode: {}\nVia: {}\n", c.describe_mode(), c.describe_via()));
+    out
+}
+
+/// Full-line completions for `partial`: command names, then arguments by kind.
+/// Board ids come in as `miniverse/<board>/#` topic candidates.
+pub async fn complete(partial: &str, commands: &[CommandDef], state: &AppState, identity: &Identity) -> Vec<String> {
+    let Some((base, slot)) = locate(partial, commands) else { return Vec::new() };
+    let prefix = &partial[base..];
+    let candidates: Vec<String> = match slot {
+        None | Some(Arg::Name) => {
+            let mut names: Vec<String> = commands.iter().flat_map(|c| std::iter::once(c.name).chain(c.aliases.iter().copied())).map(str::to_string).collect();
+            names.sort();
+            names
+        }
+        Some(Arg::Words(words)) => words.iter().map(|w| w.to_string()).collect(),
+        Some(Arg::Port) => SerialBridge::list_ports().unwrap_or_default().iter().map(|p| p.index.to_string()).collect(),
+        Some(Arg::Baud) => std::iter::once("auto".to_string())
+            .chain(state.config.serial.auto_baud_rates.iter().map(|r| r.to_string()))
+            .collect(),
+        Some(Arg::Topic) => {
+            let mut topics = state.global_topics();
+            topics.extend(own_topics(state, identity));
+            topics.extend(state.boards.read().await.keys().map(|b| format!("miniverse/{}/#", b)));
+            topics
+        }
+        Some(Arg::OwnTopic) => own_topics(state, identity),
+        Some(Arg::BySub(_)) | Some(Arg::Command) | Some(Arg::Free) => Vec::new(),
+    };
+    let lower = prefix.to_lowercase();
+    let mut found: Vec<String> = Vec::new();
+    for c in candidates {
+        if c.to_lowercase().starts_with(&lower) && !found.contains(&c) {
+            found.push(c);
+        }
+    }
+    found.into_iter().map(|c| format!("{}{}", &partial[..base], c)).collect()
+}
+
+fn own_topics(state: &AppState, identity: &Identity) -> Vec<String> {
+    let Ok(subs) = state.mqtt_subs.read() else { return Vec::new() };
+    match identity.session {
+        Some(id) => subs.session(id),
+        None => subs.global().to_vec(),
+    }
+}
+
+/// Where the word being completed starts, and what kind it is (None: a command name).
+/// Nested command lines are followed down to the innermost one.
+fn locate(partial: &str, commands: &[CommandDef]) -> Option<(usize, Option<Arg>)> {
+    let mut offset = 0;
+    loop {
+        let text = &partial[offset..];
+        let tokens = tokenize(text).ok()?;
+        let fresh = tokens.is_empty() || text.ends_with(char::is_whitespace);
+        let (done, start) = if fresh { (tokens.len(), text.len()) } else { (tokens.len() - 1, tokens[tokens.len() - 1].start) };
+        if done == 0 {
+            return Some((offset + start, None));
+        }
+        let def = find(commands, &tokens[0].text.to_lowercase())?;
+        let before: Vec<String> = tokens[1..done].iter().map(|t| t.text.clone()).collect();
+        let pos = done - 1;
+        match def.slot(pos, &before)? {
+            Arg::Command => {
+                // The nested line begins at the first word of the command slot, or after the last `;` in it
+                let first = def.args.iter().position(|a| matches!(a, Arg::Command)).unwrap_or(pos) + 1;
+                let after_semi = tokens[first..done].iter().rposition(|t| !t.quoted && t.text.ends_with(';')).map(|i| first + i + 1);
+                offset += tokens.get(after_semi.unwrap_or(first)).filter(|t| t.start < start).map_or(start, |t| t.start);
+            }
+            arg => return Some((offset + start, Some(arg))),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::auth::{self, Identity};
+use crate::events::SystemEvent;
+use crate::state::AppState;
+use crate::websocket::EventFilter;
+
+/// The newest broadcast events, numbered in send order, for `Last-Event-ID` resume
+pub struct Replay {
+    capacity: usize,
+    // Id of the most recent event; the first one is 1
+    last: u64,
+    events: VecDeque<(u64, SystemEvent)>,
+}
+
+// Upper bound on `events.replay_size`; every buffered event is a full copy
+pub const MAX_REPLAY_SIZE: usize = 10_000;
+
+impl Replay {
+    pub fn new(capacity: usize) -> Self {
+        if capacity > MAX_REPLAY_SIZE {
+            log::warn!("events.replay_size {} is above the limit; keeping {}", capacity, MAX_REPLAY_SIZE);
+        }
+        let capacity = capacity.min(MAX_REPLAY_SIZE);
+        Self { capacity, last: 0, events: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Number the next event and keep a copy of it; `None` (replay off) only takes the id
+    pub fn push(&mut self, event: Option<SystemEvent>) {
+        self.last += 1;
+        let Some(event) = event.filter(|_| self.capacity > 0) else { return };
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back((self.last, event));
+    }
+
+    /// Where a stream that has seen everything up to `after` picks up
+    pub fn resume(&self, after: Option<u64>, rx: Receiver<SystemEvent>) -> Resume {
+        // An id from before a restart can't be resumed; start fresh
+        let after = after.filter(|&id| id <= self.last).unwrap_or(self.last);
+        let oldest = self.events.front().map_or(self.last + 1, |(id, _)| *id);
+        Resume {
+            backlog: self.events.iter().filter(|(id, _)| *id > after).cloned().collect(),
+            missed: oldest.saturating_sub(after + 1),
+            next_id: self.last + 1,
+            rx,
+        }
+    }
+}
+
+/// A receiver plus what was broadcast before it subscribed
+pub struct Resume {
+    backlog: VecDeque<(u64, SystemEvent)>,
+    // Asked-for events already gone from the buffer
+    missed: u64,
+    // Id of the first event `rx` will yield
+    next_id: u64,
+    rx: Receiver<SystemEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    // Comma-separated, like the WebSocket `set_filter` lists
+    events: Option<String>,
+    topics: Option<String>,
+    boards: Option<String>,
+}
+
+// A comment line now and then stops proxies from closing an idle stream
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// GET /api/events?events=&topics=&boards=: the event broadcast as Server-Sent Events.
+/// Each event's `id` can be sent back as `Last-Event-ID` to replay what was missed.
+pub async fn events_route(
+    req: HttpRequest,
+    query: web::Query<EventsQuery>,
+    identity: web::ReqData<Identity>,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let list = |v: &Option<String>| -> Vec<String> {
+        v.as_deref().unwrap_or("").split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+    };
+    let filter = EventFilter { events: list(&query.events), topics: list(&query.topics), boards: list(&query.boards) };
+    let after = match req.headers().get("last-event-id").map(|v| v.to_str().map(str::parse::<u64>)) {
+        None => None,
+        Some(Ok(Ok(id))) => Some(id),
+        Some(_) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Last-Event-ID must be an event id" })),
+    };
+    let state = state.get_ref().clone();
+    let identity = identity.into_inner();
+    let resume = state.subscribe_after(after);
+    let keepalive = tokio::time::interval(KEEPALIVE);
+
+    let seen = (filter, identity, state);
+    let stream = futures_util::stream::unfold((resume, keepalive, seen), |(mut r, mut keepalive, seen)| async move {
+        let chunk = loop {
+            if r.missed > 0 {
+                break dropped(std::mem::take(&mut r.missed));
+            }
+            if let Some((id, event)) = r.backlog.pop_front() {
+                match frame(id, &event, &seen) {
+                    Some(frame) => break frame,
+                    None => continue,
+                }
+            }
+            tokio::select! {
+                event = r.rx.recv() => match event {
+                    Ok(event) => {
+                        let id = r.next_id;
+                        r.next_id += 1;
+                        if let Some(frame) = frame(id, &event, &seen) {
+                            break frame;
+                        }
+                    }
+                    // Too slow: those ids are skipped, and the client told how many
+                    Err(RecvError::Lagged(n)) => {
+                        r.next_id += n;
+                        break dropped(n);
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = keepalive.tick() => break ": keepalive\n\n".to_string(),
+            }
+        };
+        Some((Ok::<_, actix_web::Error>(web::Bytes::from(chunk)), (r, keepalive, seen)))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // Keep reverse proxies from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream)
+}
+
+/// `id:` / `data:` lines for an event this stream wants and may see; replayed and live events alike
+fn frame(id: u64, event: &SystemEvent, (filter, identity, state): &(EventFilter, Identity, AppState)) -> Option<String> {
+    // Like a WebSocket session without subscriptions of its own: global MQTT topics only
+    if let SystemEvent::MqttMessage { topic, .. } = event {
+        if !state.mqtt_subs.read().is_ok_and(|s| s.wants_global(topic)) {
+            return None;
+        }
+    }
+    if !auth::may_see(identity, state, event) {
+        return None;
+    }
+    let value = serde_json::to_value(event).ok()?;
+    if !filter.is_empty() && !filter.matches(&value) {
+        return None;
+    }
+    Some(format!("id: {}\ndata: {}\n\n", id, value))
+}
+
+// No id, so the client's Last-Event-ID stays put
+fn dropped(count: u64) -> String {
+    let event = SystemEvent::EventsDropped { count };
+    format!("data: {}\n\n", serde_json::to_string(&event).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    fn replay_of(capacity: usize, count: usize) -> Replay {
+        let mut replay = Replay::new(capacity);
+        for i in 1..=count {
+            replay.push(Some(SystemEvent::Output { content: format!("event {}", i) }));
+        }
+        replay
+    }
+
+    fn resume(replay: &Replay, after: Option<u64>) -> Resume {
+        replay.resume(after, broadcast::channel(4).1)
+    }
+
+    fn ids(r: &Resume) -> Vec<u64> {
+        r.backlog.iter().map(|(id, _)| *id).collect()
+    }
+
+    #[test]
+    fn fresh_stream_starts_after_the_newest_event() {
+        let r = resume(&replay_of(3, 5), None);
+        assert!(ids(&r).is_empty());
+        assert_eq!((r.missed, r.next_id), (0, 6));
+    }
+
+    #[test]
+    fn last_event_id_replays_what_followed() {
+        let r = resume(&replay_of(10, 5), Some(3));
+        assert_eq!(ids(&r), vec![4, 5]);
+        assert_eq!((r.missed, r.next_id), (0, 6));
+    }
+
+    #[test]
+    fn last_event_id_older_than_the_buffer_reports_the_gap() {
+        // Buffer holds 3..=5; event 2 was asked for but is gone
+        let r = resume(&replay_of(3, 5), Some(1));
+        assert_eq!(ids(&r), vec![3, 4, 5]);
+        assert_eq!(r.missed, 1);
+    }
+
+    #[test]
+    fn id_from_before_a_restart_starts_fresh() {
+        let r = resume(&replay_of(3, 2), Some(40));
+        assert!(ids(&r).is_empty());
+        assert_eq!((r.missed, r.next_id), (0, 3));
+    }
+
+    #[test]
+    fn replay_off_still_numbers_events() {
+        let mut replay = Replay::new(0);
+        for _ in 0..3 {
+            replay.push(None);
+        }
+        let r = resume(&replay, Some(1));
+        assert!(ids(&r).is_empty());
+        assert_eq!((r.missed, r.next_id), (2, 4));
+    }
+
+    #[test]
+    fn replay_size_is_capped() {
+        assert_eq!(Replay::new(MAX_REPLAY_SIZE + 1).capacity, MAX_REPLAY_SIZE);
+    }
+}
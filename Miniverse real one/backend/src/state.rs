@@ -1,7 +1,19 @@
+use crate::alerts::AlertBook;
+use crate::audit::AuditLog;
+use crate::auth::Login;
 use crate::config::Config;
 use crate::events::SystemEvent;
-use crate::mqtt::MqttManager;
-use crate::serial::SerialBridge;
+use crate::firmware::FirmwareInfo;
+use crate::history::History;
+use crate::mqtt::{Changes, MqttManager, Subscriptions};
+use crate::rules::RuleSet;
+use crate::scheduler::Scheduler;
+use crate::websocket::ClientMetrics;
+use crate::serial::{SerialBridge, SerialQueue};
+use crate::sessions::SessionInfo;
+use crate::sse::{Replay, Resume};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 
@@ -16,37 +28,101 @@ pub struct AppState {
     pub config: Arc<Config>,
     pub mqtt: Arc<RwLock<MqttManager>>,
     pub serial: Arc<RwLock<SerialBridge>>,
+    pub serial_queue: Arc<SerialQueue>, // one transaction at a time, plus the `lock` holder
     pub transport: Arc<RwLock<Transport>>, // preferred transport for device commands
-    pub mqtt_topics: Arc<RwLock<Vec<String>>>, // current subscribed topics (global)
+    // Global and per-session MQTT filters. A std lock so the WebSocket actor can read it
+    // synchronously; never held across an await.
+    pub mqtt_subs: Arc<std::sync::RwLock<Subscriptions>>,
+    pub boards: Arc<RwLock<HashMap<String, FirmwareInfo>>>, // handshake results keyed by board id
+    pub scheduler: Arc<RwLock<Scheduler>>,
+    pub rules: Arc<RwLock<RuleSet>>,
+    pub alerts: Arc<RwLock<AlertBook>>,
+    pub audit: Arc<AuditLog>,
+    // Per-user command lines. A std lock: the WebSocket actor expands `!n` synchronously
+    pub history: Arc<std::sync::RwLock<History>>,
+    pub logins: Arc<RwLock<HashMap<String, Login>>>, // POST /api/login session tokens
+    pub clients: Arc<std::sync::RwLock<BTreeMap<u64, Arc<ClientMetrics>>>>, // per-session delivery metrics
+    pub sessions: Arc<std::sync::RwLock<BTreeMap<u64, SessionInfo>>>, // presence list for `who`
+    pub started_at: chrono::DateTime<chrono::Local>,
+    next_session: Arc<AtomicU64>,
     event_tx: broadcast::Sender<SystemEvent>,
+    // Recent events for SSE resume. Also held while sending, so ids follow the channel's order
+    replay: Arc<std::sync::Mutex<Replay>>,
 }
 
 impl AppState {
     pub fn new(config: Config, mqtt: MqttManager, serial: SerialBridge) -> Self {
-        let (tx, _) = broadcast::channel(100);
+        let (tx, _) = broadcast::channel(config.events.channel_capacity.max(1));
+        let replay = Replay::new(config.events.replay_size);
+
+        let scheduler = Scheduler::load(&config.scheduler);
+        let rules = RuleSet::load(&config.rules);
+        let audit = AuditLog::open(&config.audit);
+        let history = History::load(&config.history);
 
         Self {
             config: Arc::new(config),
             mqtt: Arc::new(RwLock::new(mqtt)),
             serial: Arc::new(RwLock::new(serial)),
+            serial_queue: Arc::new(SerialQueue::default()),
             transport: Arc::new(RwLock::new(Transport::Serial)),
-            mqtt_topics: Arc::new(RwLock::new(Vec::new())),
+            mqtt_subs: Arc::new(std::sync::RwLock::new(Subscriptions::default())),
+            boards: Arc::new(RwLock::new(HashMap::new())),
+            scheduler: Arc::new(RwLock::new(scheduler)),
+            rules: Arc::new(RwLock::new(rules)),
+            alerts: Arc::new(RwLock::new(AlertBook::new())),
+            audit: Arc::new(audit),
+            history: Arc::new(std::sync::RwLock::new(history)),
+            logins: Arc::new(RwLock::new(HashMap::new())),
+            clients: Arc::new(std::sync::RwLock::new(BTreeMap::new())),
+            sessions: Arc::new(std::sync::RwLock::new(BTreeMap::new())),
+            started_at: chrono::Local::now(),
+            next_session: Arc::new(AtomicU64::new(1)),
             event_tx: tx,
+            replay: Arc::new(std::sync::Mutex::new(replay)),
         }
     }
 
     pub async fn init_defaults(&self) {
-        // Initialize default MQTT topics once at startup
-        let mut topics = self.mqtt_topics.write().await;
-        if topics.is_empty() {
-            *topics = self.config.mqtt.default_topics.clone();
+        // Subscribe to the configured global topics once at startup
+        for topic in &self.config.mqtt.default_topics {
+            let _ = self.update_subscriptions(|s| Ok(s.add_global(topic))).await;
         }
     }
 
+    /// Change the subscription table, then bring the broker in line with it
+    pub async fn update_subscriptions(&self, change: impl FnOnce(&mut Subscriptions) -> Result<Changes, String>) -> Result<(), String> {
+        let changes = {
+            let mut subs = self.mqtt_subs.write().map_err(|_| "Subscription table poisoned".to_string())?;
+            change(&mut subs)?
+        };
+        changes.apply(&*self.mqtt.read().await).await;
+        Ok(())
+    }
+
+    pub fn global_topics(&self) -> Vec<String> {
+        self.mqtt_subs.read().map(|s| s.global().to_vec()).unwrap_or_default()
+    }
+
+    pub fn next_session_id(&self) -> u64 {
+        self.next_session.fetch_add(1, Ordering::Relaxed)
+    }
+
     pub fn broadcast(&self, event: SystemEvent) {
+        // Copy before locking. The send stays under the lock: SSE streams number live events
+        // by arrival, so sends must happen in id order.
+        let copy = (self.config.events.replay_size > 0).then(|| event.clone());
+        let mut replay = self.replay.lock().unwrap_or_else(|e| e.into_inner());
+        replay.push(copy);
         let _ = self.event_tx.send(event);
     }
 
+    /// Subscribe and take the buffered events after `after` in one step, so none are missed or repeated
+    pub fn subscribe_after(&self, after: Option<u64>) -> Resume {
+        let replay = self.replay.lock().unwrap_or_else(|e| e.into_inner());
+        replay.resume(after, self.event_tx.subscribe())
+    }
+
     pub fn subscribe(&self) -> broadcast::Receiver<SystemEvent> {
         self.event_tx.subscribe()
     }
@@ -1,29 +1,338 @@
 use actix::{Actor, ActorContext, AsyncContext, Handler, StreamHandler};
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
+use crate::auth::{self, Identity};
 use crate::events::{ClientCommand, SystemEvent};
+use crate::scripts::parse_duration_ms;
+use crate::serial::check_mode;
+use crate::sessions::{valid_nickname, SessionInfo};
+use crate::shell::{Invocation, Spec};
 use crate::state::{AppState, Transport};
+use crate::websocket::filter::EventFilter;
+use crate::websocket::metrics::ClientMetrics;
+use crate::websocket::protocol::{Incoming, Protocol};
 use crate::websocket::handler::handle_command;
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+const TRANSPORT: Spec = Spec::new("transport <serial|mqtt>", 1, Some(1));
+const WATCH: Spec = Spec::new("watch [<interval> <command>]", 0, None).options_first();
+const UNWATCH: Spec = Spec::new("unwatch <id|all>", 1, Some(1));
+const NICK: Spec = Spec::new("nick [name | -]", 0, Some(1));
+
 pub struct WsConnection {
+    id: u64,
     hb: Instant,
     state: AppState,
+    identity: Identity,
+    protocol: Protocol,
     transport: Transport,
-    is_config: bool,
+    watches: BTreeMap<u32, Watch>,
+    next_watch_id: u32,
+    filter: EventFilter,
+    metrics: Arc<ClientMetrics>,
+    nickname: Option<String>,
+    connected_at: chrono::DateTime<chrono::Local>,
+}
+
+/// A `watch` polling task owned by this connection
+struct Watch {
+    interval_ms: u64,
+    command: String,
+    task: JoinHandle<()>,
 }
 
 impl WsConnection {
-    pub fn new(state: AppState) -> Self {
+    pub fn new(state: AppState, mut identity: Identity, nickname: Option<String>, protocol: Protocol) -> Self {
+        let id = state.next_session_id();
+        identity.session = Some(id);
+        let metrics = Arc::new(ClientMetrics::new(&identity.user));
         Self {
+            id,
+            metrics,
             hb: Instant::now(),
             state,
+            identity,
+            protocol,
             transport: Transport::Serial,
-            is_config: false,
+            watches: BTreeMap::new(),
+            next_watch_id: 1,
+            filter: EventFilter::default(),
+            nickname,
+            connected_at: chrono::Local::now(),
+        }
+    }
+
+    /// Publish this session's current transport and boards to the presence list.
+    /// The mode lives only there, so the dispatcher can check it for scripts and watches too.
+    fn sync_presence(&self) {
+        let Ok(mut sessions) = self.state.sessions.write() else { return };
+        let mode = sessions.get(&self.id).map(|s| s.mode).unwrap_or_default();
+        let info = SessionInfo {
+            session: self.id,
+            user: self.identity.user.clone(),
+            nickname: self.nickname.clone(),
+            transport: match self.transport { Transport::Serial => "serial", Transport::Mqtt => "mqtt" }.to_string(),
+            mode,
+            boards: self.filter.boards.clone(),
+            connected_at: self.connected_at,
+        };
+        sessions.insert(self.id, info);
+    }
+
+    /// Answer request `id` directly
+    fn reply(&self, ctx: &mut ws::WebsocketContext<Self>, id: Option<&str>, event: &SystemEvent) {
+        if let Some(json) = self.protocol.reply(id, event) {
+            ctx.text(json);
+        }
+    }
+
+    /// Replace a `!n` recall with the command it names (echoing it), and record the line
+    fn recall(&self, id: Option<&str>, command: &mut String, ctx: &mut ws::WebsocketContext<Self>) -> Result<(), String> {
+        let Ok(mut history) = self.state.history.write() else { return Ok(()) };
+        if let Some(expanded) = history.expand(&self.identity.user, command) {
+            *command = expanded?;
+            let echo = SystemEvent::Output { content: command.clone() };
+            self.reply(ctx, id, &echo);
+        }
+        if !command.trim().is_empty() {
+            if let Err(e) = history.push(&self.identity.user, command.trim()) {
+                log::error!("History: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer what this session handles itself (history, roles, transport, filters, watches,
+    /// identity). Anything else comes back for the shared dispatcher.
+    fn answer_locally(&mut self, id: Option<&str>, mut cmd: ClientCommand, ctx: &mut ws::WebsocketContext<Self>) -> Result<Vec<SystemEvent>, ClientCommand> {
+        // Server-side history: expand `!n` recalls, then remember the line
+        if let ClientCommand::Command { command } = &mut cmd {
+            if let Err(e) = self.recall(id, command, ctx) {
+                let err = SystemEvent::Error { source: "history".into(), message: e };
+                return Ok(vec![err]);
+            }
+        }
+
+        let needed = auth::required_role(&cmd);
+        let what = match &cmd {
+            ClientCommand::Command { command } => command.split_whitespace().next().unwrap_or("").to_lowercase(),
+            ClientCommand::ChangeMode { mode } => format!("mode {}", mode),
+            ClientCommand::Subscribe { .. } => "subscribe".to_string(),
+            ClientCommand::Publish { .. } => "publish".to_string(),
+            ClientCommand::SetFilter { .. } | ClientCommand::ClearFilter => "filter".to_string(),
+            ClientCommand::Complete { .. } => "complete".to_string(),
+        };
+        if let Err(message) = self.identity.allows(needed, &what) {
+            log::warn!("Denied {} for {}", what, self.identity.user);
+            let err = SystemEvent::Error { source: "auth".into(), message };
+            return Ok(vec![err]);
+        }
+
+        // Terminal lines that don't tokenize fall through; the dispatcher reports them
+        let inv = match &cmd {
+            ClientCommand::Command { command } => Invocation::parse(command).ok(),
+            _ => None,
+        };
+
+        // Intercept session-scoped controls
+        if let Some(inv) = inv.as_ref().filter(|i| i.name() == "transport") {
+            if let Err(err) = check_mode(inv, &self.state, &self.identity) {
+                return Ok(vec![err]);
+            }
+            let args = match inv.args(&TRANSPORT) {
+                Ok(a) => a,
+                Err(message) => {
+                    let err = SystemEvent::Error { source: "cli".into(), message };
+                    return Ok(vec![err]);
+                }
+            };
+            let replies = match args.get(0).unwrap_or("").to_lowercase().as_str() {
+                "serial" => {
+                    self.transport = Transport::Serial;
+                    let evt = SystemEvent::TransportChanged { transport: "serial".into(), publish_topic: "".into(), subscribe_topics: vec![], board_id: None };
+                    let ok = SystemEvent::Output { content: "Transport: serial".into() };
+                    vec![evt, ok]
+                }
+                "mqtt" => {
+                    self.transport = Transport::Mqtt;
+                    let publish = "miniverse/command".to_string();
+                    let subs = self.state.global_topics();
+                    let evt = SystemEvent::TransportChanged { transport: "mqtt".into(), publish_topic: publish, subscribe_topics: subs, board_id: None };
+                    let ok = SystemEvent::Output { content: "Transport: mqtt".into() };
+                    vec![evt, ok]
+                }
+                other => {
+                    let err = SystemEvent::Error { source: "cli".into(), message: args.error_at(0, &format!("Unknown transport '{}'", other)) };
+                    return Ok(vec![err]);
+                }
+            };
+            self.sync_presence();
+            return Ok(replies);
+        }
+
+        // Session event filter
+        match cmd {
+            ClientCommand::SetFilter { topics, boards, events } => {
+                self.filter = EventFilter { topics, boards, events };
+                self.sync_presence();
+                let evt = SystemEvent::Output { content: self.filter.describe() };
+                return Ok(vec![evt]);
+            }
+            ClientCommand::ClearFilter => {
+                self.filter = EventFilter::default();
+                self.sync_presence();
+                let evt = SystemEvent::Output { content: self.filter.describe() };
+                return Ok(vec![evt]);
+            }
+            _ => {}
+        }
+
+        // Session-owned polling tasks and identity
+        if let Some(inv) = &inv {
+            let head = inv.name();
+            if head == "whoami" {
+                let nick = self.nickname.as_ref().map(|n| format!(" as {}", n)).unwrap_or_default();
+                let evt = SystemEvent::Output { content: format!("{}, session #{}{}", self.identity.describe(), self.id, nick) };
+                return Ok(vec![evt]);
+            }
+            if head == "nick" {
+                let evt = self.handle_nick(inv);
+                return Ok(vec![evt]);
+            }
+            if head == "watch" || head == "unwatch" {
+                let evt = self.handle_watch(inv, ctx);
+                return Ok(vec![evt]);
+            }
+        }
+
+        Err(cmd)
+    }
+
+    /// `nick` shows the current nickname, `nick <name>` sets it, `nick -` clears it
+    fn handle_nick(&mut self, inv: &Invocation) -> SystemEvent {
+        let args = match inv.args(&NICK) {
+            Ok(a) => a,
+            Err(message) => return SystemEvent::Error { source: "cli".into(), message },
+        };
+        match args.get(0).unwrap_or("") {
+            "" => SystemEvent::Output { content: format!("Nickname: {}", self.nickname.as_deref().unwrap_or("(none)")) },
+            "-" => {
+                self.nickname = None;
+                self.sync_presence();
+                SystemEvent::Output { content: "Nickname cleared".into() }
+            }
+            nick if valid_nickname(nick) => {
+                self.nickname = Some(nick.to_string());
+                self.sync_presence();
+                SystemEvent::Output { content: format!("Nickname set to {}", nick) }
+            }
+            _ => SystemEvent::Error { source: "session".into(), message: args.error_at(0, "Nickname must be 1-24 letters, digits, '-', '_' or '.'") },
+        }
+    }
+
+    /// `watch <interval> <command>`, `watch` (list) and `unwatch <id|all>`
+    fn handle_watch(&mut self, inv: &Invocation, ctx: &mut ws::WebsocketContext<Self>) -> SystemEvent {
+        if inv.name() == "unwatch" {
+            let args = match inv.args(&UNWATCH) {
+                Ok(a) => a,
+                Err(message) => return SystemEvent::Error { source: "cli".into(), message },
+            };
+            let target = args.get(0).unwrap_or("");
+            if target.eq_ignore_ascii_case("all") {
+                let n = self.watches.len();
+                self.stop_watches();
+                return SystemEvent::Output { content: format!("Stopped {} watch(es)", n) };
+            }
+            return match target.parse::<u32>().ok().and_then(|id| self.watches.remove(&id).map(|w| (id, w))) {
+                Some((id, w)) => {
+                    w.task.abort();
+                    SystemEvent::Output { content: format!("Watch #{} stopped", id) }
+                }
+                None => SystemEvent::Error { source: "watch".into(), message: args.error_at(0, "No such watch (see 'watch')") },
+            };
+        }
+
+        let args = match inv.args(&WATCH) {
+            Ok(a) => a,
+            Err(message) => return SystemEvent::Error { source: "cli".into(), message },
+        };
+        let interval = args.get(0).unwrap_or("");
+        let mut target = args.rest(1);
+        if interval.is_empty() || interval.eq_ignore_ascii_case("list") {
+            if self.watches.is_empty() {
+                return SystemEvent::Output { content: "No active watches".into() };
+            }
+            let lines: Vec<String> = self
+                .watches
+                .iter()
+                .map(|(id, w)| format!("#{} every {}ms: {}", id, w.interval_ms, w.command))
+                .collect();
+            return SystemEvent::Output { content: format!("Watches ({}):\n{}", lines.len(), lines.join("\n")) };
+        }
+
+        let cfg = &self.state.config.watch;
+        let interval_ms = match parse_duration_ms(interval) {
+            Some(ms) if !target.is_empty() => ms,
+            Some(_) => return SystemEvent::Error { source: "watch".into(), message: args.error_at(1, "Missing command to watch (e.g. watch 2s distance)") },
+            None => return SystemEvent::Error { source: "watch".into(), message: args.error_at(0, "Invalid interval (e.g. 500ms, 2s, 1m)") },
+        };
+        if interval_ms < cfg.min_interval_ms {
+            return SystemEvent::Error { source: "watch".into(), message: format!("Interval must be at least {}ms", cfg.min_interval_ms) };
+        }
+        if self.watches.len() >= cfg.max_per_session {
+            return SystemEvent::Error { source: "watch".into(), message: format!("At most {} watches per session; use 'unwatch <id>' first", cfg.max_per_session) };
+        }
+        // A `!n` recall watches the command it names, checked like a typed one
+        let recalled = self.state.history.read().ok().and_then(|h| h.expand(&self.identity.user, &target));
+        match recalled {
+            Some(Ok(command)) => target = command,
+            Some(Err(e)) => return SystemEvent::Error { source: "history".into(), message: args.error_at(1, &e) },
+            None => {}
+        }
+        let head = target.split_whitespace().next().unwrap_or("").to_lowercase();
+        if matches!(head.as_str(), "watch" | "unwatch" | "transport") {
+            return SystemEvent::Error { source: "watch".into(), message: format!("'{}' cannot be watched", head) };
+        }
+
+        let id = self.next_watch_id;
+        self.next_watch_id += 1;
+        let addr = ctx.address();
+        let state = self.state.clone();
+        let transport = self.transport;
+        let identity = self.identity.clone();
+        let protocol = self.protocol;
+        let cmd = target.clone();
+        let task = actix::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+            // A command slower than the interval delays the next run instead of triggering a burst
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if !addr.connected() {
+                    break;
+                }
+                let response = handle_command(ClientCommand::Command { command: cmd.clone() }, &state, Some(transport), &identity).await;
+                if let Some(json) = protocol.event(&response) {
+                    addr.do_send(SendMessage(json));
+                }
+            }
+        });
+        self.watches.insert(id, Watch { interval_ms, command: target.clone(), task });
+        SystemEvent::Output { content: format!("Watch #{} started: every {}ms: {} (unwatch {} to stop)", id, interval_ms, target, id) }
+    }
+
+    fn stop_watches(&mut self) {
+        for (_, w) in std::mem::take(&mut self.watches) {
+            w.task.abort();
         }
     }
 
@@ -46,24 +355,38 @@ impl Actor for WsConnection {
         log::info!("WebSocket connection started");
         self.hb(ctx);
 
-        self.state.broadcast(SystemEvent::Connected);
+        // The new client learns its own session id; everyone else sees it join
+        if let Some(json) = self.protocol.hello(self.id, &self.identity.user, self.nickname.as_deref()) {
+            ctx.text(json);
+        }
+        self.sync_presence();
+        self.state.broadcast(SystemEvent::Joined { session: self.id, user: self.identity.user.clone(), nickname: self.nickname.clone() });
 
-        let addr = ctx.address();
-        let state = self.state.clone();
+        if let Ok(mut clients) = self.state.clients.write() {
+            clients.insert(self.id, self.metrics.clone());
+        }
 
+        let addr = ctx.address();
+        let metrics = self.metrics.clone();
+        let mut rx = self.state.subscribe();
         actix::spawn(async move {
-            let mut rx = state.subscribe();
             loop {
-                match rx.recv().await {
+                let event = rx.recv().await;
+                if !addr.connected() {
+                    break;
+                }
+                match event {
                     Ok(event) => {
-                        if let Ok(json) = serde_json::to_string(&event) {
-                            addr.do_send(BroadcastMessage(json));
-                        }
+                        metrics.queue_depth(rx.len());
+                        addr.do_send(BroadcastMessage(event));
                     }
-                    Err(e) => {
-                        log::error!("Event channel error: {}", e);
-                        break;
+                    // Fell behind: the oldest events are gone, carry on from what's left
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("WebSocket client lagged, dropped {} events", n);
+                        metrics.lagged(n);
+                        addr.do_send(BroadcastMessage(SystemEvent::EventsDropped { count: n }));
                     }
+                    Err(RecvError::Closed) => break,
                 }
             }
         });
@@ -71,6 +394,24 @@ impl Actor for WsConnection {
 
     fn stopped(&mut self, _: &mut Self::Context) {
         log::info!("WebSocket connection stopped");
+        self.stop_watches();
+        if let Ok(mut clients) = self.state.clients.write() {
+            clients.remove(&self.id);
+        }
+        if let Ok(mut sessions) = self.state.sessions.write() {
+            sessions.remove(&self.id);
+        }
+        self.state.broadcast(SystemEvent::Left { session: self.id, user: self.identity.user.clone(), nickname: self.nickname.clone() });
+        if let Some(h) = self.state.serial_queue.release_session(self.id) {
+            log::info!("Released serial lock held by {} (session closed)", h.user);
+        }
+
+        // Give back this session's MQTT subscriptions
+        let state = self.state.clone();
+        let id = self.id;
+        actix::spawn(async move {
+            let _ = state.update_subscriptions(|s| Ok(s.drop_session(id))).await;
+        });
     }
 }
 
@@ -87,69 +428,44 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
             Ok(ws::Message::Text(text)) => {
                 log::debug!("Received: {}", text);
 
-                match serde_json::from_str::<ClientCommand>(&text) {
-                    Ok(cmd) => {
-                        // Intercept session-scoped controls
-                        if let ClientCommand::Command { command } = &cmd {
-                            let lc = command.trim().to_lowercase();
-                            if lc.starts_with("transport ") {
-                                if !self.is_config {
-                                    let err = SystemEvent::Error { source: "command".into(), message: "'transport' is allowed only in CONFIG mode".into() };
-                                    if let Ok(json) = serde_json::to_string(&err) { ctx.text(json); }
-                                    return;
+                match self.protocol.decode(&text) {
+                    Ok(Incoming::Ping { id }) => {
+                        self.hb = Instant::now();
+                        if let Some(json) = self.protocol.pong(id.as_deref()) {
+                            ctx.text(json);
+                        }
+                    }
+                    Ok(Incoming::Command { id, command }) => {
+                        let cmd = match self.answer_locally(id.as_deref(), command, ctx) {
+                            Ok(replies) => {
+                                for reply in &replies {
+                                    self.reply(ctx, id.as_deref(), reply);
                                 }
-                                let mode = lc.split_whitespace().nth(1).unwrap_or("");
-                                match mode {
-                                    "serial" => {
-                                        self.transport = Transport::Serial;
-                                        let evt = SystemEvent::TransportChanged { transport: "serial".into(), publish_topic: "".into(), subscribe_topics: vec![], board_id: None };
-                                        if let Ok(json) = serde_json::to_string(&evt) { ctx.text(json); }
-                                        let ok = SystemEvent::Output { content: "Transport: serial".into() };
-                                        if let Ok(json) = serde_json::to_string(&ok) { ctx.text(json); }
-                                    }
-                                    "mqtt" => {
-                                        self.transport = Transport::Mqtt;
-                                        let publish = "miniverse/command".to_string();
-                                        // NOTE: we cannot access config here synchronously; keep default
-                                        let subs = vec!["miniverse/#".to_string()];
-                                        let evt = SystemEvent::TransportChanged { transport: "mqtt".into(), publish_topic: publish, subscribe_topics: subs, board_id: None };
-                                        if let Ok(json) = serde_json::to_string(&evt) { ctx.text(json); }
-                                        let ok = SystemEvent::Output { content: "Transport: mqtt".into() };
-                                        if let Ok(json) = serde_json::to_string(&ok) { ctx.text(json); }
-                                    }
-                                    _ => {
-                                        let out = SystemEvent::Output { content: "Usage: transport <serial|mqtt>".into() };
-                                        if let Ok(json) = serde_json::to_string(&out) { ctx.text(json); }
-                                    }
+                                if let Some(json) = self.protocol.done(id.as_deref(), !replies.iter().any(is_error)) {
+                                    ctx.text(json);
                                 }
-                                return; // handled here
+                                return;
                             }
-                        }
-
-                        // Track session mode updates
-                        if let ClientCommand::ChangeMode { mode } = &cmd {
-                            self.is_config = mode.eq_ignore_ascii_case("config");
-                        }
-
+                            Err(cmd) => cmd,
+                        };
                         let state = self.state.clone();
                         let addr = ctx.address();
                         let transport = self.transport;
+                        let identity = self.identity.clone();
+                        let protocol = self.protocol;
                         actix::spawn(async move {
-                            let response = handle_command(cmd, &state, Some(transport)).await;
-                            if let Ok(json) = serde_json::to_string(&response) {
+                            let response = handle_command(cmd, &state, Some(transport), &identity).await;
+                            if let Some(json) = protocol.reply(id.as_deref(), &response) {
+                                addr.do_send(SendMessage(json));
+                            }
+                            if let Some(json) = protocol.done(id.as_deref(), !is_error(&response)) {
                                 addr.do_send(SendMessage(json));
                             }
                         });
                     }
-                    Err(e) => {
-                        log::error!("Parse error: {}", e);
-                        let error = SystemEvent::Error {
-                            source: "websocket".to_string(),
-                            message: format!("Invalid command format: {}", e),
-                        };
-                        if let Ok(json) = serde_json::to_string(&error) {
-                            ctx.text(json);
-                        }
+                    Err(frame) => {
+                        log::error!("Parse error: {}", text);
+                        ctx.text(frame);
                     }
                 }
             }
@@ -166,6 +482,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
     }
 }
 
+fn is_error(event: &SystemEvent) -> bool {
+    matches!(event, SystemEvent::Error { .. })
+}
+
 #[derive(actix::Message)]
 #[rtype(result = "()")]
 struct SendMessage(String);
@@ -180,21 +500,61 @@ impl Handler<SendMessage> for WsConnection {
 
 #[derive(actix::Message)]
 #[rtype(result = "()")]
-struct BroadcastMessage(String);
+struct BroadcastMessage(SystemEvent);
 
 impl Handler<BroadcastMessage> for WsConnection {
     type Result = ();
 
     fn handle(&mut self, msg: BroadcastMessage, ctx: &mut Self::Context) {
-        ctx.text(msg.0);
+        // MQTT traffic only reaches sessions that subscribed to it, or when it's on a global topic
+        if let SystemEvent::MqttMessage { topic, .. } = &msg.0 {
+            if !self.state.mqtt_subs.read().is_ok_and(|s| s.wants(self.id, topic)) {
+                self.metrics.filtered();
+                return;
+            }
+        }
+        if !auth::may_see(&self.identity, &self.state, &msg.0) {
+            self.metrics.filtered();
+            return;
+        }
+        if !self.filter.is_empty() && !matches!(msg.0, SystemEvent::EventsDropped { .. }) {
+            let Ok(value) = serde_json::to_value(&msg.0) else { return };
+            if !self.filter.matches(&value) {
+                self.metrics.filtered();
+                return;
+            }
+        }
+        if let Some(json) = self.protocol.event(&msg.0) {
+            self.metrics.delivered();
+            ctx.text(json);
+        }
     }
 }
 
-pub async fn ws_route(
-    req: HttpRequest,
-    stream: web::Payload,
-    state: web::Data<AppState>,
-) -> Result<HttpResponse, Error> {
+pub async fn ws_route(req: HttpRequest, stream: web::Payload, state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    start(req, stream, state, Protocol::V1).await
+}
+
+/// `/ws/v2`: the same session, with request ids, typed responses and app-level ping
+pub async fn ws_v2_route(req: HttpRequest, stream: web::Payload, state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    start(req, stream, state, Protocol::V2).await
+}
+
+async fn start(req: HttpRequest, stream: web::Payload, state: web::Data<AppState>, protocol: Protocol) -> Result<HttpResponse, Error> {
     let state = state.get_ref().clone();
-    ws::start(WsConnection::new(state), &req, stream)
+    // Reject before the upgrade so clients get a real 401 instead of a dropped socket
+    let identity = match auth::authenticate(&req, &state).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            log::warn!("Rejected WebSocket upgrade: {}", e);
+            return Ok(auth::unauthorized(&e));
+        }
+    };
+    // Optional `?nick=` so a client can name itself up front
+    let nickname = web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|q| q.get("nick").cloned())
+        .filter(|n| valid_nickname(n));
+    log::info!("WebSocket v{} session for {}", protocol.version(), identity.user);
+    ws::start(WsConnection::new(state, identity, nickname, protocol), &req, stream)
 }
@@ -0,0 +1,94 @@
+use serde_json::Value;
+
+use crate::mqtt::topic_matches;
+
+/// What a session wants from the shared event stream. An empty list leaves that dimension open.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    // MQTT filters applied to `mqtt_message` topics
+    pub topics: Vec<String>,
+    // Board ids; events that carry no board always pass
+    pub boards: Vec<String>,
+    // Event `type` names, e.g. `reading`, `mqtt_message`
+    pub events: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        self.topics.is_empty() && self.boards.is_empty() && self.events.is_empty()
+    }
+
+    /// Test an already-serialized event
+    pub fn matches(&self, event: &Value) -> bool {
+        let kind = event.get("type").and_then(Value::as_str).unwrap_or("");
+        if !self.events.is_empty() && !self.events.iter().any(|e| e == kind) {
+            return false;
+        }
+
+        let topic = event.get("topic").and_then(Value::as_str);
+        if let (false, Some(topic)) = (self.topics.is_empty(), topic) {
+            if !self.topics.iter().any(|f| topic_matches(f, topic)) {
+                return false;
+            }
+        }
+
+        if !self.boards.is_empty() {
+            let board = event
+                .get("board")
+                .and_then(Value::as_str)
+                .or_else(|| topic.and_then(|t| t.strip_prefix("miniverse/")).and_then(|t| t.split('/').next()));
+            if let Some(board) = board {
+                return self.boards.iter().any(|b| b.eq_ignore_ascii_case(board));
+            }
+        }
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "Event filter: none (all events)".to_string();
+        }
+        let show = |v: &Vec<String>| if v.is_empty() { "*".to_string() } else { v.join(", ") };
+        format!(
+            "Event filter:\n  events: {}\n  topics: {}\n  boards: {}",
+            show(&self.events),
+            show(&self.topics),
+            show(&self.boards)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn list(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_filter_passes_everything() {
+        let filter = EventFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches(&json!({ "type": "reading", "board": "b1" })));
+    }
+
+    #[test]
+    fn event_types_and_topics() {
+        let filter = EventFilter { events: list(&["mqtt_message"]), topics: list(&["miniverse/+/temp/#"]), boards: vec![] };
+        assert!(filter.matches(&json!({ "type": "mqtt_message", "topic": "miniverse/b1/temp/state" })));
+        assert!(!filter.matches(&json!({ "type": "mqtt_message", "topic": "miniverse/b1/led/state" })));
+        assert!(!filter.matches(&json!({ "type": "reading", "board": "b1" })));
+    }
+
+    #[test]
+    fn boards_come_from_the_event_or_its_topic() {
+        let filter = EventFilter { boards: list(&["B1"]), ..Default::default() };
+        assert!(filter.matches(&json!({ "type": "reading", "board": "b1" })));
+        assert!(filter.matches(&json!({ "type": "mqtt_message", "topic": "miniverse/b1/temp/state" })));
+        assert!(!filter.matches(&json!({ "type": "reading", "board": "b2" })));
+        // No board at all: not filtered out
+        assert!(filter.matches(&json!({ "type": "output", "content": "hi" })));
+    }
+}
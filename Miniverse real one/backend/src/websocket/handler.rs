@@ -1,24 +1,85 @@
+use crate::audit::AuditEntry;
+use crate::auth::{self, Access, Identity};
 use crate::events::{ClientCommand, SystemEvent};
-use crate::serial::handle_serial_command_with_transport;
+use crate::sessions;
+use crate::serial::{command_target, complete_command, handle_serial_command_with_transport};
 use crate::state::AppState;
 use crate::state::Transport;
 
-pub async fn handle_command(cmd: ClientCommand, state: &AppState, transport: Option<Transport>) -> SystemEvent {
+/// Run one client command and record it in the audit log
+pub async fn handle_command(cmd: ClientCommand, state: &AppState, transport: Option<Transport>, identity: &Identity) -> SystemEvent {
+    // Completion only reads; not worth an audit entry per Tab press
+    if let ClientCommand::Complete { partial } = cmd {
+        let items = complete_command(&partial, state, identity).await;
+        return SystemEvent::Completions { partial, items };
+    }
+    let effective = match transport {
+        Some(t) => t,
+        None => *state.transport.read().await,
+    };
+    let (kind, target, payload) = match &cmd {
+        ClientCommand::Command { command } => ("command", command_target(command, state, effective).await, command.clone()),
+        ClientCommand::ChangeMode { mode } => ("mode", String::new(), mode.clone()),
+        ClientCommand::Subscribe { topic } => ("subscribe", topic.clone(), String::new()),
+        ClientCommand::Publish { topic, payload } => ("publish", topic.clone(), payload.clone()),
+        ClientCommand::SetFilter { .. } | ClientCommand::ClearFilter => ("filter", String::new(), String::new()),
+        ClientCommand::Complete { partial } => ("complete", String::new(), partial.clone()),
+    };
+    let transport_name = match (&cmd, effective) {
+        (ClientCommand::Subscribe { .. } | ClientCommand::Publish { .. }, _) | (_, Transport::Mqtt) => "mqtt",
+        (_, Transport::Serial) => "serial",
+    };
+
+    let response = dispatch(cmd, state, transport, identity).await;
+
+    let (ok, result) = match &response {
+        SystemEvent::Error { message, .. } => (false, message.clone()),
+        SystemEvent::Output { content } => (true, content.clone()),
+        other => (true, serde_json::to_string(other).unwrap_or_default()),
+    };
+    state.audit.record(AuditEntry {
+        id: 0,
+        at: chrono::Local::now(),
+        session: identity.session,
+        user: identity.user.clone(),
+        transport: transport_name.to_string(),
+        kind: kind.to_string(),
+        target,
+        payload,
+        ok,
+        result,
+    });
+    response
+}
+
+async fn dispatch(cmd: ClientCommand, state: &AppState, transport: Option<Transport>, identity: &Identity) -> SystemEvent {
+    // Topic ACLs apply before anything reaches the MQTT client
+    let acl = match &cmd {
+        ClientCommand::Subscribe { topic } => auth::check_topic(identity, state, Access::Subscribe, topic),
+        ClientCommand::Publish { topic, .. } => auth::check_topic(identity, state, Access::Publish, topic),
+        _ => Ok(()),
+    };
+    if let Err(message) = acl {
+        return SystemEvent::Error { source: "acl".to_string(), message };
+    }
+
     match cmd {
         ClientCommand::Command { command } => {
-            handle_serial_command_with_transport(&command, state, transport).await
+            handle_serial_command_with_transport(&command, state, transport, identity).await
         }
         
-        ClientCommand::ChangeMode { mode } => {
-            state.broadcast(SystemEvent::ModeChanged { mode: mode.clone() });
-            SystemEvent::Output {
-                content: format!("Mode changed to: {}", mode),
-            }
-        }
+        // Same state machine as the `config` / `exit` commands; only this session changes
+        ClientCommand::ChangeMode { mode } => match sessions::set_mode(state, identity, &mode) {
+            Ok(mode) => SystemEvent::ModeChanged { mode: mode.name().to_string() },
+            Err(message) => SystemEvent::Error { source: "mode".to_string(), message },
+        },
         
         ClientCommand::Subscribe { topic } => {
-            let mqtt = state.mqtt.read().await;
-            match mqtt.subscribe(&topic).await {
+            let result = match identity.session {
+                Some(id) => state.update_subscriptions(|s| s.add(id, &topic)).await,
+                None => state.update_subscriptions(|s| Ok(s.add_global(&topic))).await,
+            };
+            match result {
                 Ok(_) => SystemEvent::Output {
                     content: format!("Subscribed: {}", topic),
                 },
@@ -29,6 +90,15 @@ pub async fn handle_command(cmd: ClientCommand, state: &AppState, transport: Opt
             }
         }
         
+        // Filters belong to a WebSocket session, which intercepts these before dispatch
+        ClientCommand::SetFilter { .. } | ClientCommand::ClearFilter => SystemEvent::Error {
+            source: "websocket".to_string(),
+            message: "Event filters are per WebSocket session".to_string(),
+        },
+
+        // Answered in handle_command
+        ClientCommand::Complete { partial } => SystemEvent::Completions { partial, items: Vec::new() },
+
         ClientCommand::Publish { topic, payload } => {
             let mqtt = state.mqtt.read().await;
             match mqtt.publish(&topic, payload.as_bytes()).await {
@@ -0,0 +1,103 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::auth::{Identity, Role};
+use crate::state::AppState;
+
+/// Delivery counters for one WebSocket client, shared by its actor and forwarding task
+#[derive(Debug)]
+pub struct ClientMetrics {
+    user: String,
+    connected_at: DateTime<Local>,
+    delivered: AtomicU64,
+    // Skipped by the session's event filter or MQTT subscriptions
+    filtered: AtomicU64,
+    // Lost because the client fell more than the channel capacity behind
+    dropped: AtomicU64,
+    lag_events: AtomicU64,
+    // Events waiting in this client's receiver after the last read
+    pending: AtomicU64,
+    max_pending: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub session: u64,
+    pub user: String,
+    pub connected_at: DateTime<Local>,
+    pub delivered: u64,
+    pub filtered: u64,
+    pub dropped: u64,
+    pub lag_events: u64,
+    pub pending: u64,
+    pub max_pending: u64,
+}
+
+impl ClientMetrics {
+    pub fn new(user: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            connected_at: Local::now(),
+            delivered: AtomicU64::new(0),
+            filtered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            lag_events: AtomicU64::new(0),
+            pending: AtomicU64::new(0),
+            max_pending: AtomicU64::new(0),
+        }
+    }
+
+    pub fn delivered(&self) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn filtered(&self) {
+        self.filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn lagged(&self, dropped: u64) {
+        self.dropped.fetch_add(dropped, Ordering::Relaxed);
+        self.lag_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn queue_depth(&self, pending: usize) {
+        let pending = pending as u64;
+        self.pending.store(pending, Ordering::Relaxed);
+        self.max_pending.fetch_max(pending, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, session: u64) -> MetricsSnapshot {
+        MetricsSnapshot {
+            session,
+            user: self.user.clone(),
+            connected_at: self.connected_at,
+            delivered: self.delivered.load(Ordering::Relaxed),
+            filtered: self.filtered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            lag_events: self.lag_events.load(Ordering::Relaxed),
+            pending: self.pending.load(Ordering::Relaxed),
+            max_pending: self.max_pending.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// GET /api/metrics
+pub async fn metrics_route(identity: web::ReqData<Identity>, state: web::Data<AppState>) -> HttpResponse {
+    // Lists every session's user and traffic
+    if identity.role < Role::Admin {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Client metrics require the admin role (you are {})", identity.role)
+        }));
+    }
+    let clients: Vec<MetricsSnapshot> = state
+        .clients
+        .read()
+        .map(|c| c.iter().map(|(id, m)| m.snapshot(*id)).collect())
+        .unwrap_or_default();
+    HttpResponse::Ok().json(serde_json::json!({
+        "channel_capacity": state.config.events.channel_capacity,
+        "clients": clients,
+    }))
+}
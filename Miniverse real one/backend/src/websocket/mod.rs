@@ -1,4 +1,10 @@
 mod connection;
+mod filter;
 mod handler;
+mod metrics;
+mod protocol;
 
-pub use connection::ws_route;
+pub use connection::{ws_route, ws_v2_route};
+pub use filter::EventFilter;
+pub use metrics::{metrics_route, ClientMetrics};
+pub use protocol::{Done, Reply};
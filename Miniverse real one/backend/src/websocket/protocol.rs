@@ -0,0 +1,200 @@
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::events::{ClientCommand, ClientRequest, SystemEvent, PROTOCOL_VERSION};
+
+/// Wire format of a WebSocket session. `/ws` (v1) sends bare `SystemEvent`s and takes bare
+/// `ClientCommand`s; `/ws/v2` wraps both so replies carry the request id and can't be
+/// mistaken for broadcast events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    V1,
+    V2,
+}
+
+/// What a client sent, whichever protocol it speaks
+pub enum Incoming {
+    Command { id: Option<String>, command: ClientCommand },
+    Ping { id: Option<String> },
+}
+
+/// v2 client frames
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum ClientFrame {
+    // App-level keepalive; browsers can't see WebSocket ping frames
+    #[serde(rename = "ping")]
+    Ping {
+        #[serde(default)]
+        id: Option<String>,
+    },
+
+    #[serde(rename = "request")]
+    Request { id: String, command: ClientCommand },
+}
+
+/// v1 direct reply: the event with the request's id alongside its fields.
+/// Not `id`, which alert, rule and job events already use for their own.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Reply<'a> {
+    request_id: &'a str,
+    #[serde(flatten)]
+    event: &'a SystemEvent,
+}
+
+/// v1 end of a request's replies
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "type", rename = "done")]
+pub struct Done<'a> {
+    request_id: &'a str,
+    ok: bool,
+}
+
+/// v2 server frames
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+enum ServerFrame<'a> {
+    // First frame on a new session
+    #[serde(rename = "hello")]
+    Hello {
+        version: u32,
+        protocol: &'a str,
+        session: u64,
+        user: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nickname: Option<&'a str>,
+    },
+
+    #[serde(rename = "pong")]
+    Pong {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<&'a str>,
+        at: DateTime<Local>,
+    },
+
+    // The answer to one request; `ok` is false when `result` is an error event
+    #[serde(rename = "response")]
+    Response { id: &'a str, ok: bool, result: &'a SystemEvent },
+
+    // Anything not asked for: broadcasts, watch ticks, presence
+    #[serde(rename = "event")]
+    Event { event: &'a SystemEvent },
+
+    // Nothing more will follow for request `id`
+    #[serde(rename = "done")]
+    Done { id: &'a str, ok: bool },
+
+    // The frame itself couldn't be understood
+    #[serde(rename = "error")]
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<&'a str>,
+        message: String,
+    },
+}
+
+impl Protocol {
+    pub fn version(self) -> u32 {
+        match self {
+            Protocol::V1 => 1,
+            Protocol::V2 => 2,
+        }
+    }
+
+    /// Parse one text frame; on failure, the error frame to send back
+    pub fn decode(self, text: &str) -> Result<Incoming, String> {
+        let decoded = match self {
+            Protocol::V1 => serde_json::from_str::<ClientRequest>(text).map(|ClientRequest { id, command }| Incoming::Command { id, command }),
+            Protocol::V2 => serde_json::from_str::<ClientFrame>(text).map(|frame| match frame {
+                ClientFrame::Ping { id } => Incoming::Ping { id },
+                ClientFrame::Request { id, command } => Incoming::Command { id: Some(id), command },
+            }),
+        };
+        decoded.map_err(|e| {
+            // Still correlate the error when the id itself was readable
+            let id = serde_json::from_str::<serde_json::Value>(text)
+                .ok()
+                .and_then(|v| v.get("id").and_then(|i| i.as_str()).map(str::to_string));
+            self.invalid(id.as_deref(), &e.to_string())
+        })
+    }
+
+    /// First message of a session
+    pub fn hello(self, session: u64, user: &str, nickname: Option<&str>) -> Option<String> {
+        match self {
+            Protocol::V1 => self.event(&SystemEvent::Connected {
+                session,
+                user: user.to_string(),
+                protocol: PROTOCOL_VERSION.to_string(),
+                nickname: nickname.map(str::to_string),
+            }),
+            Protocol::V2 => serde_json::to_string(&ServerFrame::Hello { version: self.version(), protocol: PROTOCOL_VERSION, session, user, nickname }).ok(),
+        }
+    }
+
+    pub fn pong(self, id: Option<&str>) -> Option<String> {
+        serde_json::to_string(&ServerFrame::Pong { id, at: Local::now() }).ok()
+    }
+
+    /// An event the client didn't ask for
+    pub fn event(self, event: &SystemEvent) -> Option<String> {
+        match self {
+            Protocol::V1 => serde_json::to_string(event).ok(),
+            Protocol::V2 => serde_json::to_string(&ServerFrame::Event { event }).ok(),
+        }
+    }
+
+    /// The answer to request `id`; without an id it goes out as a plain event
+    pub fn reply(self, id: Option<&str>, result: &SystemEvent) -> Option<String> {
+        match (self, id) {
+            (Protocol::V1, Some(id)) => serde_json::to_string(&Reply { request_id: id, event: result }).ok(),
+            (Protocol::V2, Some(id)) => {
+                let ok = !matches!(result, SystemEvent::Error { .. });
+                serde_json::to_string(&ServerFrame::Response { id, ok, result }).ok()
+            }
+            (_, None) => self.event(result),
+        }
+    }
+
+    /// Sent after the last reply to request `id`; nothing for requests without one
+    pub fn done(self, id: Option<&str>, ok: bool) -> Option<String> {
+        let id = id?;
+        match self {
+            Protocol::V1 => serde_json::to_string(&Done { request_id: id, ok }).ok(),
+            Protocol::V2 => serde_json::to_string(&ServerFrame::Done { id, ok }).ok(),
+        }
+    }
+
+    fn invalid(self, id: Option<&str>, detail: &str) -> String {
+        match self {
+            Protocol::V1 => self
+                .reply(id, &SystemEvent::Error { source: "websocket".to_string(), message: format!("Invalid command format: {}", detail) })
+                .unwrap_or_default(),
+            Protocol::V2 => {
+                serde_json::to_string(&ServerFrame::Error { id, message: format!("Invalid frame: {}", detail) }).unwrap_or_default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_reply_keeps_the_event_id_apart_from_the_request_id() {
+        let event = SystemEvent::AlertAcked { id: 7, name: "hot".to_string(), board: "b1".to_string() };
+        let json = Protocol::V1.reply(Some("req-1"), &event).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["request_id"], "req-1");
+        assert_eq!(value["id"], 7);
+        assert_eq!(json.matches("\"id\"").count(), 1);
+    }
+
+    #[test]
+    fn v1_done_carries_the_request_id() {
+        let value: serde_json::Value = serde_json::from_str(&Protocol::V1.done(Some("req-1"), true).unwrap()).unwrap();
+        assert_eq!(value, serde_json::json!({ "type": "done", "request_id": "req-1", "ok": true }));
+    }
+}
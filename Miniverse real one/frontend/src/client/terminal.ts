@@ -1,6 +1,6 @@
 import { Terminal } from '@xterm/xterm';
 import { FitAddon } from '@xterm/addon-fit';
-import { wsClient, type SystemEvent, type SensorDetail } from '../lib/websocket';
+import { wsClient, PROTOCOL_VERSION, type SystemEvent, type SensorDetail } from '../lib/websocket';
 
 type Mode = 'normal' | 'config';
 
@@ -69,12 +69,9 @@ export class TerminalManager {
         this.currentLine = '';
         this.writeln('');
         this.prompt();
-      // Tab => autocomplete from history (latest match)
+      // Tab => ask the backend; the answer arrives as a 'completions' event
       } else if (code === 9) {
-        const suggestion = this.autocompleteFromHistory(this.currentLine);
-        if (suggestion && suggestion !== this.currentLine) {
-          this.replaceCurrentLine(suggestion);
-        }
+        wsClient.complete(this.currentLine);
       // Arrow keys (escape sequences)
       } else if (code === 27) {
         const seq = data.slice(1);
@@ -100,8 +97,19 @@ export class TerminalManager {
   private handleEvent(e: SystemEvent) {
     switch (e.type) {
       case 'connected':
+        // Recall works across devices and reloads
+        wsClient.fetchHistory().then((h) => {
+          this.history = h;
+          this.historyIndex = h.length;
+        }).catch(() => {});
+        // Every new session starts in NORMAL mode on the server
+        this.mode = 'normal';
         this.writeln('');
-        this.writeln(`\x1b[38;2;0;200;0m[OK]\x1b[0m Connected to Miniverse Backend`);
+        this.writeln(`\x1b[38;2;0;200;0m[OK]\x1b[0m Connected to Miniverse Backend (session #${e.session})`);
+        if (e.protocol.split('.')[0] !== PROTOCOL_VERSION.split('.')[0]) {
+          this.writeln(`\x1b[33m[WARN]\x1b[0m Backend speaks protocol ${e.protocol}, this page expects ${PROTOCOL_VERSION}; reload to update`);
+        }
+        this.pendingPrompt = false;
         this.prompt();
         break;
         
@@ -111,7 +119,7 @@ export class TerminalManager {
           this.serialPort = e.port;
           this.boardName = e.board_name;
           this.writeln('');
-          this.writeln(`\x1b[38;2;0;200;0m[OK]\x1b[0m Serial: ${e.port} - ${e.board_name} @ ${e.baud_rate} baud`);
+          this.writeln(`\x1b[38;2;0;200;0m[OK]\x1b[0m Serial: ${e.port} - ${e.board_name} @ ${e.baud_rate} baud${e.auto_baud ? ' (auto)' : ''}`);
         } else {
           this.serialPort = '';
           this.boardName = '';
@@ -181,6 +189,47 @@ export class TerminalManager {
         break;
       }
         
+      case 'script_step':
+        this.writeln('');
+        this.writeln(`${e.ok ? '\x1b[38;2;0;200;0m[OK]\x1b[0m' : '\x1b[31m[ERR]\x1b[0m'} ${e.script}:${e.line} ${e.command} – ${e.output}`);
+        break;
+
+      case 'rule_fired':
+        this.writeln('');
+        this.writeln(`${e.ok ? '\x1b[38;2;0;200;0m[OK]\x1b[0m' : '\x1b[31m[ERR]\x1b[0m'} rule #${e.id} (${e.trigger}) – ${e.output}`);
+        break;
+
+      case 'job_run':
+        this.writeln('');
+        this.writeln(`${e.ok ? '\x1b[38;2;0;200;0m[OK]\x1b[0m' : '\x1b[31m[ERR]\x1b[0m'} job #${e.id} ${e.action} – ${e.output}`);
+        break;
+
+      case 'completions':
+        this.showCompletions(e.partial, e.items);
+        break;
+
+      case 'joined':
+      case 'left':
+        this.writeln('');
+        this.writeln(`\x1b[90m[SESSION]\x1b[0m #${e.session} ${e.nickname ? `${e.nickname} (${e.user})` : e.user} ${e.type}`);
+        break;
+
+      case 'events_dropped':
+        this.writeln('');
+        this.writeln(`\x1b[33m[WARN]\x1b[0m dropped ${e.count} events (client fell behind)`);
+        break;
+
+      case 'alert_raised':
+        this.writeln('');
+        this.writeln(`\x1b[31m[ALERT]\x1b[0m #${e.id} ${e.name} (${e.board}) – ${e.message}`);
+        break;
+
+      case 'alert_acked':
+      case 'alert_resolved':
+        this.writeln('');
+        this.writeln(`\x1b[33m[ALERT]\x1b[0m #${e.id} ${e.name} (${e.board}) ${e.type === 'alert_acked' ? 'acknowledged' : 'resolved'}`);
+        break;
+
       case 'mqtt_message':
   this.writeln('');
   {
@@ -206,14 +255,7 @@ export class TerminalManager {
     this.history.push(trimmed);
     this.historyIndex = this.history.length;
 
-    // Client-side validation: allow only known patterns
-    if (!this.isAllowedCommand(trimmed)) {
-      this.writeln('\x1b[31m[ERR]\x1b[0m Unknown command. Type `help` for available commands.');
-      this.prompt();
-      return;
-    }
-
-    // Local commands
+    // The only local command; the backend validates everything else, modes included
     if (trimmed === 'clear') {
       this.term.clear();
       this.pendingPrompt = false;
@@ -221,37 +263,6 @@ export class TerminalManager {
       return;
     }
 
-    if (trimmed === 'help') {
-      this.showHelp();
-      this.prompt();
-      return;
-    }
-
-    // Guard: transport commands allowed only in config mode
-    if (trimmed.toLowerCase().startsWith('transport ')
-        && this.mode !== 'config') {
-      this.writeln('');
-      this.writeln('\x1b[31m[ERR]\x1b[0m transport is available only in CONFIG mode. Type `config` first.');
-      this.prompt();
-      return;
-    }
-
-    if (trimmed === 'config') {
-      this.mode = 'config';
-      wsClient.changeMode('config');
-      this.pendingPrompt = false;
-      this.prompt();
-      return;
-    }
-
-    if (trimmed === 'normal' || trimmed === 'exit') {
-      this.mode = 'normal';
-      wsClient.changeMode('normal');
-      this.pendingPrompt = false;
-      this.prompt();
-      return;
-    }
-
     // Provide guidance if user tries Arduino commands without a serial connection
     const looksArduino = ['temp','distance','light','set','lcd']
       .some(k => trimmed.startsWith(k));
@@ -266,35 +277,6 @@ export class TerminalManager {
     wsClient.sendCommand(trimmed);
   }
 
-  private isAllowedCommand(line: string): boolean {
-    const m = this.mode;
-    const low = line.toLowerCase();
-    // always allowed
-    if (['help','clear','config','normal','exit'].includes(low)) return true;
-    // config-mode commands
-    const isConfig = (
-      low.startsWith('ports') ||
-      low.startsWith('connect ') || low === 'connect' ||
-      low.startsWith('disconnect') || low.startsWith('status') ||
-      low.startsWith('transport serial') || low.startsWith('transport mqtt') ||
-      low.startsWith('mqtt sub ') || low.startsWith('mqtt unsub ') || low === 'mqtt subs'
-    );
-    if (m === 'config' && isConfig) return true;
-
-    // normal-mode commands
-    const isNormal = (
-      low === 'info' || low === 'about' || low === 'version' ||
-      low === 'temp' || low.startsWith('temp ') ||
-      low === 'distance' || low.startsWith('distance ') ||
-      low.startsWith('set light ') ||
-      low === 'light on' || low === 'light off' ||
-      low === 'led on' || low === 'led off' ||
-      low === 'lcd clear' || low.startsWith('lcd show ')
-    );
-    if (m === 'normal' && isNormal) return true;
-    return false;
-  }
-
   private replaceCurrentLine(text: string) {
     const promptText = this.getPromptText();
     this.term.write(`\r\x1b[K${promptText}`);
@@ -302,6 +284,33 @@ export class TerminalManager {
     this.term.write(text);
   }
 
+  private showCompletions(partial: string, items: string[]) {
+    // The user kept typing while the request was in flight
+    if (partial !== this.currentLine) return;
+    if (items.length === 0) {
+      const suggestion = this.autocompleteFromHistory(partial);
+      if (suggestion && suggestion !== partial) this.replaceCurrentLine(suggestion);
+      return;
+    }
+    if (items.length === 1) {
+      this.replaceCurrentLine(`${items[0]} `);
+      return;
+    }
+    let common = items[0];
+    for (const item of items) {
+      while (!item.startsWith(common)) common = common.slice(0, -1);
+    }
+    if (common.length > partial.length) {
+      this.replaceCurrentLine(common);
+      return;
+    }
+    // Show only the word being completed, then restore the line
+    const start = partial.length - (partial.match(/\S*$/)?.[0].length ?? 0);
+    this.writeln('');
+    this.writeln(items.map((i) => i.slice(start)).join('  '));
+    this.term.write(`${this.getPromptText()}${this.currentLine}`);
+  }
+
   private autocompleteFromHistory(prefix: string): string | null {
     if (!prefix) return null;
     for (let i = this.history.length - 1; i >= 0; i--) {
@@ -375,38 +384,6 @@ export class TerminalManager {
     return null;
   }
 
-  private showHelp() {
-    this.writeln('');
-    this.writeln('MINIVERSE COMMANDS');
-    this.writeln('==================');
-    this.writeln('');
-    this.writeln('System:');
-    this.writeln('  help           - Show this help');
-    this.writeln('  clear          - Clear screen');
-    this.writeln('  config         - Enter config mode');
-    this.writeln('  normal         - Enter normal mode');
-    this.writeln('');
-    this.writeln('Config Mode:');
-    this.writeln('  ports                  - List serial ports');
-    this.writeln('  connect <n> [baud]     - Connect to port');
-    this.writeln('  disconnect             - Disconnect serial');
-    this.writeln('  status                 - Show status');
-    this.writeln('  transport serial|mqtt  - Select routing (CONFIG only)');
-    this.writeln('  mqtt sub <topic>       - Subscribe to topic');
-  this.writeln('  mqtt unsub <topic>     - Unsubscribe from topic');
-  this.writeln('  mqtt subs              - List current subscriptions');
-    this.writeln('');
-    this.writeln('Normal Mode:');
-  this.writeln('  temp                   - Read temperature (unit chosen by firmware)');
-    this.writeln('  distance [id]          - Read distance');
-    this.writeln('  light on | light off   - LED shortcut full/zero');
-    this.writeln('  set light <0-255> [color] - Set LED brightness');
-    this.writeln('  lcd clear              - Clear LCD');
-    this.writeln('  lcd show "a" ["b"]   - Show text on LCD');
-    this.writeln('  info | about | version');
-    this.writeln('');
-  }
-
   // Public API for external UI controls (e.g., status bar Clear button)
   public clear(showWelcome: boolean = true) {
     this.term.clear();
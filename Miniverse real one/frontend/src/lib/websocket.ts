@@ -1,12 +1,31 @@
+// Schema version these types follow; the backend serves the authoritative one at /api/schema
+export const PROTOCOL_VERSION = '2.0.0';
+
 export type SystemEvent = 
   | { type: 'mqtt_message'; topic: string; payload: string }
-  | { type: 'serial_status'; connected: boolean; port: string | null; baud_rate: number | null; board_name: string | null }
-  | { type: 'sensor_info'; sensors: SensorDetail[]; board: string; firmware: string }
+  | { type: 'serial_status'; connected: boolean; port: string | null; baud_rate: number | null; board_name: string | null; auto_baud?: boolean }
+  | { type: 'sensor_info'; sensors: SensorDetail[]; board: string; firmware: string; capabilities?: string[] }
   | { type: 'output'; content: string }
   | { type: 'error'; source: string; message: string }
-  | { type: 'connected' }
+  | { type: 'connected'; session: number; user: string; protocol: string; nickname?: string }
+  | { type: 'joined'; session: number; user: string; nickname?: string }
+  | { type: 'left'; session: number; user: string; nickname?: string }
   | { type: 'mode_changed'; mode: string }
-  | { type: 'transport_changed'; transport: string; publish_topic: string; subscribe_topics: string[]; board_id?: string };
+  | { type: 'completions'; partial: string; items: string[] }
+  | { type: 'transport_changed'; transport: string; publish_topic: string; subscribe_topics: string[]; board_id?: string }
+  | { type: 'events_dropped'; count: number }
+  | { type: 'script_step'; script: string; user: string; line: number; command: string; ok: boolean; output: string }
+  | { type: 'script_finished'; script: string; executed: number; failed: number; stopped: boolean }
+  | { type: 'reading'; board: string; sensor: string; value: number; unit: string }
+  | { type: 'rule_fired'; id: number; rule: string; trigger: string; ok: boolean; output: string }
+  | { type: 'job_run'; id: number; action: string; ok: boolean; output: string }
+  | { type: 'alert_raised'; id: number; name: string; board: string; message: string }
+  | { type: 'alert_acked'; id: number; name: string; board: string }
+  | { type: 'alert_resolved'; id: number; name: string; board: string };
+
+// v1 answers to a command sent with an `id`
+export type Reply = SystemEvent & { request_id: string };
+export type Done = { type: 'done'; request_id: string; ok: boolean };
 
 export interface SensorDetail {
   id: number;
@@ -14,11 +33,17 @@ export interface SensorDetail {
   pin: string;
 }
 
-export type ClientCommand =
+// `id` is optional on every command; the direct reply carries it as `request_id`
+// (events have ids of their own), then `{ type: 'done', request_id, ok }` follows
+export type ClientCommand = (
   | { type: 'command'; command: string }
   | { type: 'mode'; mode: string }
   | { type: 'subscribe'; topic: string }
-  | { type: 'publish'; topic: string; payload: string };
+  | { type: 'publish'; topic: string; payload: string }
+  | { type: 'set_filter'; topics?: string[]; boards?: string[]; events?: string[] }
+  | { type: 'clear_filter' }
+  | { type: 'complete'; partial: string }
+) & { id?: string };
 
 export class WebSocketClient {
   private ws: WebSocket | null = null;
@@ -32,7 +57,9 @@ export class WebSocketClient {
 
   async connect(): Promise<void> {
     return new Promise((resolve, reject) => {
-      this.ws = new WebSocket(this.url);
+      // Token from POST /api/login or a configured API token, when the backend has auth enabled
+      const token = localStorage.getItem('miniverse_token');
+      this.ws = new WebSocket(token ? `${this.url}?token=${encodeURIComponent(token)}` : this.url);
 
       this.ws.onopen = () => {
         console.log('✓ WebSocket connected');
@@ -75,6 +102,30 @@ export class WebSocketClient {
     this.send({ type: 'mode', mode });
   }
 
+  setFilter(filter: { topics?: string[]; boards?: string[]; events?: string[] }) {
+    this.send({ type: 'set_filter', ...filter });
+  }
+
+  clearFilter() {
+    this.send({ type: 'clear_filter' });
+  }
+
+  complete(partial: string) {
+    this.send({ type: 'complete', partial });
+  }
+
+  // Server-side command history for the logged-in user (oldest first)
+  async fetchHistory(limit = 100): Promise<string[]> {
+    const base = this.url.replace(/^ws/, 'http').replace(/\/ws$/, '');
+    const token = localStorage.getItem('miniverse_token');
+    const res = await fetch(`${base}/api/history?limit=${limit}`, {
+      headers: token ? { Authorization: `Bearer ${token}` } : {},
+    });
+    if (!res.ok) return [];
+    const entries = (await res.json()) as { n: number; command: string }[];
+    return entries.map((e) => e.command);
+  }
+
   on(type: string, handler: (e: SystemEvent) => void) {
     if (!this.handlers.has(type)) {
       this.handlers.set(type, new Set());